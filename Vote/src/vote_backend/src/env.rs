@@ -0,0 +1,164 @@
+// The system API the canister uses. Outside tests every function forwards to
+// `ic_cdk`; under `cfg(test)` they act on an in-process replica stand-in so
+// endpoints can be called directly from unit tests.
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::Principal;
+use ic_cdk::api::call::CallResult;
+use std::time::Duration;
+
+#[cfg(test)]
+pub(crate) use fake::*;
+#[cfg(not(test))]
+pub(crate) use replica::*;
+
+#[cfg(not(test))]
+mod replica {
+    use super::*;
+
+    pub(crate) fn caller() -> Principal {
+        ic_cdk::caller()
+    }
+
+    pub(crate) fn time() -> u64 {
+        ic_cdk::api::time()
+    }
+
+    pub(crate) fn canister_balance() -> u64 {
+        ic_cdk::api::canister_balance()
+    }
+
+    pub(crate) fn set_certified_data(data: &[u8]) {
+        ic_cdk::api::set_certified_data(data)
+    }
+
+    pub(crate) fn data_certificate() -> Option<Vec<u8>> {
+        ic_cdk::api::data_certificate()
+    }
+
+    pub(crate) fn trap(message: &str) -> ! {
+        ic_cdk::trap(message)
+    }
+
+    pub(crate) async fn call<T: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>>(
+        id: Principal,
+        method: &str,
+        args: T,
+    ) -> CallResult<R> {
+        ic_cdk::call(id, method, args).await
+    }
+
+    pub(crate) async fn call_raw(id: Principal, method: &str, args: &[u8]) -> CallResult<Vec<u8>> {
+        ic_cdk::api::call::call_raw(id, method, args, 0).await
+    }
+
+    pub(crate) fn notify<T: ArgumentEncoder>(id: Principal, method: &str, args: T) {
+        let _ = ic_cdk::notify(id, method, args);
+    }
+
+    pub(crate) fn set_timer_interval(interval: Duration, func: impl FnMut() + 'static) {
+        ic_cdk_timers::set_timer_interval(interval, func);
+    }
+}
+
+#[cfg(test)]
+mod fake {
+    use super::*;
+    use ic_cdk::api::call::RejectionCode;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+
+    type Handler = Box<dyn Fn(&[u8]) -> CallResult<Vec<u8>>>;
+
+    // Mid-2023, so "n nanoseconds ago" never underflows in tests.
+    pub(crate) const START_TIME: u64 = 1_690_000_000_000_000_000;
+
+    thread_local! {
+        static CALLER: Cell<Principal> = const { Cell::new(Principal::anonymous()) };
+        static TIME: Cell<u64> = const { Cell::new(START_TIME) };
+        static BALANCE: Cell<u64> = const { Cell::new(10_000_000_000_000) };
+        static CERTIFIED_DATA: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+        static HANDLERS: RefCell<HashMap<(Principal, String), Handler>> = RefCell::new(HashMap::new());
+        static NOTIFICATIONS: RefCell<Vec<(Principal, String, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(crate) fn caller() -> Principal {
+        CALLER.with(Cell::get)
+    }
+
+    pub(crate) fn time() -> u64 {
+        TIME.with(Cell::get)
+    }
+
+    pub(crate) fn canister_balance() -> u64 {
+        BALANCE.with(Cell::get)
+    }
+
+    pub(crate) fn set_certified_data(data: &[u8]) {
+        CERTIFIED_DATA.with(|c| *c.borrow_mut() = data.to_vec());
+    }
+
+    // Certificates only exist for replicated queries on a real subnet.
+    pub(crate) fn data_certificate() -> Option<Vec<u8>> {
+        None
+    }
+
+    pub(crate) fn trap(message: &str) -> ! {
+        panic!("{}", message)
+    }
+
+    pub(crate) async fn call<T: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>>(
+        id: Principal,
+        method: &str,
+        args: T,
+    ) -> CallResult<R> {
+        let args = candid::utils::encode_args(args).expect("failed to encode call arguments");
+        let reply = call_raw(id, method, &args).await?;
+        candid::utils::decode_args(&reply)
+            .map_err(|err| (RejectionCode::CanisterError, err.to_string()))
+    }
+
+    pub(crate) async fn call_raw(id: Principal, method: &str, args: &[u8]) -> CallResult<Vec<u8>> {
+        HANDLERS.with(|h| match h.borrow().get(&(id, method.to_string())) {
+            Some(handler) => handler(args),
+            None => Err((RejectionCode::DestinationInvalid, format!("no {method} on {id}"))),
+        })
+    }
+
+    pub(crate) fn notify<T: ArgumentEncoder>(id: Principal, method: &str, args: T) {
+        let args = candid::utils::encode_args(args).expect("failed to encode notify arguments");
+        NOTIFICATIONS.with(|n| n.borrow_mut().push((id, method.to_string(), args)));
+    }
+
+    // Timers never fire here; tests call what a timer would run themselves.
+    pub(crate) fn set_timer_interval(_interval: Duration, _func: impl FnMut() + 'static) {}
+
+    pub(crate) fn set_caller(caller: Principal) {
+        CALLER.with(|c| c.set(caller));
+    }
+
+    pub(crate) fn advance_time(ns: u64) {
+        TIME.with(|t| t.set(t.get() + ns));
+    }
+
+    pub(crate) fn set_balance(cycles: u64) {
+        BALANCE.with(|b| b.set(cycles));
+    }
+
+    pub(crate) fn certified_data() -> Vec<u8> {
+        CERTIFIED_DATA.with(|c| c.borrow().clone())
+    }
+
+    // Answers every later call to `method` on `id` with `handler`'s raw reply.
+    pub(crate) fn on_call(
+        id: Principal,
+        method: &str,
+        handler: impl Fn(&[u8]) -> CallResult<Vec<u8>> + 'static,
+    ) {
+        HANDLERS.with(|h| h.borrow_mut().insert((id, method.to_string()), Box::new(handler)));
+    }
+
+    // Drains the one-way messages sent so far.
+    pub(crate) fn take_notifications() -> Vec<(Principal, String, Vec<u8>)> {
+        NOTIFICATIONS.with(|n| n.borrow_mut().drain(..).collect())
+    }
+}
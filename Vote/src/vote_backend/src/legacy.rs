@@ -0,0 +1,400 @@
+// Stored values as earlier releases wrote them. Each mirror keeps the fields its
+// type had from the start and has every field added since as an `Option`, which
+// candid reads as `None` when the field is missing. A field added to a stored
+// type has to be added to its mirror the same way. Counters and weights were
+// nat32 until they were widened to nat64, so mirrors keep them as `u32`.
+use super::*;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::Memory as _;
+
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyProposal {
+    description: String,
+    approve: u32,
+    reject: u32,
+    pass: u32,
+    is_active: bool,
+    voted: Vec<candid::Principal>,
+    owner: candid::Principal,
+    private_voters: Option<bool>,
+    binary: Option<bool>,
+    quorum_pct: Option<u8>,
+    created_at: Option<u64>,
+    updated_at: Option<u64>,
+    owner_vote_counts: Option<bool>,
+    weighted: Option<bool>,
+    tags: Option<Vec<String>>,
+    secret_hash: Option<[u8; 32]>,
+    attachments: Option<Vec<Attachment>>,
+    human_id: Option<String>,
+    reached_at: Option<OptionTimes>,
+    quorum: Option<u64>,
+    threshold_pct: Option<u8>,
+    min_winning_votes: Option<u64>,
+    listed: Option<bool>,
+    lock_on_first_vote: Option<bool>,
+    execution: Option<ExecutionPayload>,
+    executed: Option<bool>,
+    private_tally: Option<bool>,
+    anonymous_ballots: Option<u32>,
+    deadline: Option<u64>,
+    vote_group_id: Option<u64>,
+}
+
+// Missing settings get the values `new_proposal` gives a proposal created
+// without them.
+impl From<LegacyProposal> for Proposal {
+    fn from(old: LegacyProposal) -> Self {
+        Proposal {
+            description: old.description,
+            approve: old.approve.into(),
+            reject: old.reject.into(),
+            pass: old.pass.into(),
+            is_active: old.is_active,
+            voted: old.voted,
+            owner: old.owner,
+            private_voters: old.private_voters.unwrap_or(false),
+            binary: old.binary.unwrap_or(false),
+            quorum_pct: old.quorum_pct,
+            created_at: old.created_at.unwrap_or(0),
+            updated_at: old.updated_at.unwrap_or(0),
+            owner_vote_counts: old.owner_vote_counts.unwrap_or(true),
+            weighted: old.weighted.unwrap_or(false),
+            tags: old.tags.unwrap_or_default(),
+            secret_hash: old.secret_hash,
+            attachments: old.attachments.unwrap_or_default(),
+            human_id: old.human_id,
+            reached_at: old.reached_at.unwrap_or_default(),
+            quorum: old.quorum,
+            threshold_pct: old.threshold_pct,
+            min_winning_votes: old.min_winning_votes,
+            listed: old.listed.unwrap_or(true),
+            lock_on_first_vote: old.lock_on_first_vote.unwrap_or(false),
+            execution: old.execution,
+            executed: old.executed.unwrap_or(false),
+            private_tally: old.private_tally.unwrap_or(false),
+            anonymous_ballots: old.anonymous_ballots.unwrap_or(0),
+            deadline: old.deadline,
+            vote_group_id: old.vote_group_id,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyConfig {
+    admin: candid::Principal,
+    min_description_distinct_chars: Option<u32>,
+    min_description_words: Option<u32>,
+    subscriber: Option<candid::Principal>,
+    vote_cancellation_window_ns: Option<u64>,
+    reject_duplicate_descriptions: Option<bool>,
+    owner_unique_descriptions: Option<bool>,
+    min_scan_balance: Option<u64>,
+    require_registration: Option<bool>,
+    max_active_proposals: Option<u64>,
+    votes_per_principal: Option<u32>,
+    min_reveal_voters: Option<u32>,
+    strict_majority: Option<bool>,
+    tally_delay_ns: Option<u64>,
+    tally_history_retention_ns: Option<u64>,
+    allow_anonymous: Option<bool>,
+    invalidate_votes_on_edit: Option<bool>,
+    pow_difficulty: Option<u8>,
+    frozen: Option<bool>,
+    default_quorum_pct: Option<u8>,
+    tie_break: Option<TieBreak>,
+    min_approve_count: Option<u32>,
+    comment_policy: Option<CommentPolicy>,
+    auto_finalize: Option<bool>,
+    record_vote_timeline: Option<bool>,
+    min_account_age_ns: Option<u64>,
+    neuron_source: Option<candid::Principal>,
+    early_lock: Option<bool>,
+    max_distinct_tags: Option<u64>,
+    normalize_principals: Option<bool>,
+    vote_confirmation_window_ns: Option<u64>,
+    pass_is_turnout: Option<bool>,
+    max_description_revisions: Option<u32>,
+    default_ttl_ns: Option<u64>,
+    min_create_interval_ns: Option<u64>,
+}
+
+// Settings an older release did not have yet start out at their defaults.
+impl From<LegacyConfig> for CanisterConfig {
+    fn from(old: LegacyConfig) -> Self {
+        let default = CanisterConfig::default();
+        CanisterConfig {
+            admin: old.admin,
+            min_description_distinct_chars: old.min_description_distinct_chars,
+            min_description_words: old.min_description_words,
+            subscriber: old.subscriber,
+            vote_cancellation_window_ns: old
+                .vote_cancellation_window_ns
+                .unwrap_or(default.vote_cancellation_window_ns),
+            reject_duplicate_descriptions: old
+                .reject_duplicate_descriptions
+                .unwrap_or(default.reject_duplicate_descriptions),
+            owner_unique_descriptions: old
+                .owner_unique_descriptions
+                .unwrap_or(default.owner_unique_descriptions),
+            min_scan_balance: old.min_scan_balance,
+            require_registration: old.require_registration.unwrap_or(default.require_registration),
+            max_active_proposals: old.max_active_proposals,
+            votes_per_principal: old.votes_per_principal.unwrap_or(default.votes_per_principal),
+            min_reveal_voters: old.min_reveal_voters.unwrap_or(default.min_reveal_voters),
+            strict_majority: old.strict_majority.unwrap_or(default.strict_majority),
+            tally_delay_ns: old.tally_delay_ns.unwrap_or(default.tally_delay_ns),
+            tally_history_retention_ns: old
+                .tally_history_retention_ns
+                .unwrap_or(default.tally_history_retention_ns),
+            allow_anonymous: old.allow_anonymous.unwrap_or(default.allow_anonymous),
+            invalidate_votes_on_edit: old
+                .invalidate_votes_on_edit
+                .unwrap_or(default.invalidate_votes_on_edit),
+            pow_difficulty: old.pow_difficulty,
+            frozen: old.frozen.unwrap_or(default.frozen),
+            default_quorum_pct: old.default_quorum_pct,
+            tie_break: old.tie_break.unwrap_or(default.tie_break),
+            min_approve_count: old.min_approve_count,
+            comment_policy: old.comment_policy.unwrap_or(default.comment_policy),
+            auto_finalize: old.auto_finalize.unwrap_or(default.auto_finalize),
+            record_vote_timeline: old.record_vote_timeline.unwrap_or(default.record_vote_timeline),
+            min_account_age_ns: old.min_account_age_ns.unwrap_or(default.min_account_age_ns),
+            neuron_source: old.neuron_source,
+            early_lock: old.early_lock.unwrap_or(default.early_lock),
+            max_distinct_tags: old.max_distinct_tags,
+            normalize_principals: old.normalize_principals.unwrap_or(default.normalize_principals),
+            vote_confirmation_window_ns: old
+                .vote_confirmation_window_ns
+                .unwrap_or(default.vote_confirmation_window_ns),
+            pass_is_turnout: old.pass_is_turnout.unwrap_or(default.pass_is_turnout),
+            max_description_revisions: old
+                .max_description_revisions
+                .unwrap_or(default.max_description_revisions),
+            default_ttl_ns: old.default_ttl_ns,
+            min_create_interval_ns: old
+                .min_create_interval_ns
+                .unwrap_or(default.min_create_interval_ns),
+        }
+    }
+}
+
+// First `{choice, timestamp}`, then with the ballot's `weight`, before records
+// tracked every ballot in `cast` and `ballots`.
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyVoteRecord {
+    choice: VoteTypes,
+    timestamp: u64,
+    weight: Option<u32>,
+    cast: Option<LegacyTally>,
+    ballots: Option<u32>,
+}
+
+impl From<LegacyVoteRecord> for VoteRecord {
+    fn from(old: LegacyVoteRecord) -> Self {
+        let cast = old.cast.map_or_else(
+            || {
+                let mut cast = Tally::default();
+                cast.add(old.choice, old.weight.unwrap_or(1).into());
+                cast
+            },
+            Tally::from,
+        );
+        VoteRecord {
+            choice: old.choice,
+            timestamp: old.timestamp,
+            cast,
+            ballots: old.ballots.unwrap_or(1),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyTally {
+    approve: u32,
+    reject: u32,
+    pass: u32,
+}
+
+impl From<LegacyTally> for Tally {
+    fn from(old: LegacyTally) -> Self {
+        Tally {
+            approve: old.approve.into(),
+            reject: old.reject.into(),
+            pass: old.pass.into(),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyTallySnapshot {
+    tally: LegacyTally,
+    voters: u32,
+}
+
+impl From<LegacyTallySnapshot> for TallySnapshot {
+    fn from(old: LegacyTallySnapshot) -> Self {
+        TallySnapshot {
+            tally: old.tally.into(),
+            voters: old.voters,
+            anonymous_ballots: None,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyTimelineEntry {
+    timestamp: u64,
+    choice: VoteTypes,
+    weight: u32,
+}
+
+impl From<LegacyTimelineEntry> for TimelineEntry {
+    fn from(old: LegacyTimelineEntry) -> Self {
+        TimelineEntry {
+            timestamp: old.timestamp,
+            choice: old.choice,
+            weight: old.weight.into(),
+        }
+    }
+}
+
+// Until events recorded whether the proposal was active, `Created` and
+// `Edited` had no payload.
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyEvent {
+    proposal_key: u64,
+    caller: candid::Principal,
+    timestamp: u64,
+    kind: LegacyEventKind,
+}
+
+#[derive(CandidType, Deserialize)]
+enum LegacyEventKind {
+    Created(Option<Activity>),
+    Edited(Option<Activity>),
+    Voted { choice: VoteTypes, weight: u32 },
+    VoteCancelled,
+    VotesInvalidated,
+    Ended,
+    Activated,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Activity {
+    is_active: bool,
+}
+
+// Events logged without the activity replay as creating an active proposal and
+// leaving it active on edit, which is how proposals were used back then.
+impl From<LegacyEvent> for Event {
+    fn from(old: LegacyEvent) -> Self {
+        let is_active = |activity: Option<Activity>| activity.is_none_or(|a| a.is_active);
+        let kind = match old.kind {
+            LegacyEventKind::Created(activity) => EventKind::Created {
+                is_active: is_active(activity),
+            },
+            LegacyEventKind::Edited(activity) => EventKind::Edited {
+                is_active: is_active(activity),
+            },
+            LegacyEventKind::Voted { choice, weight } => EventKind::Voted {
+                choice,
+                weight: weight.into(),
+            },
+            LegacyEventKind::VoteCancelled => EventKind::VoteCancelled,
+            LegacyEventKind::VotesInvalidated => EventKind::VotesInvalidated,
+            LegacyEventKind::Ended => EventKind::Ended,
+            LegacyEventKind::Activated => EventKind::Activated,
+        };
+        Event {
+            proposal_key: old.proposal_key,
+            caller: old.caller,
+            timestamp: old.timestamp,
+            kind,
+            private_voters: None,
+        }
+    }
+}
+
+// Written while `VoteError::ProposalTooLarge` had no payload, which no longer
+// decodes as a `VoteError` whatever the stored error was. Replaying such an
+// error can only say that the vote failed.
+#[derive(CandidType, Deserialize)]
+pub(crate) struct LegacyIdempotentVote {
+    key_hash: [u8; 32],
+    result: Result<(), candid::Reserved>,
+}
+
+impl From<LegacyIdempotentVote> for IdempotentVote {
+    fn from(old: LegacyIdempotentVote) -> Self {
+        IdempotentVote {
+            key_hash: old.key_hash,
+            result: old.result.map_err(|_| VoteError::VoteFailed),
+        }
+    }
+}
+
+// Raw entry of a map whose header was written with a `MAX_SIZE` of `MAX`; a map
+// has to be loaded with exactly the value size it was created with.
+pub(crate) struct LegacyStored<const MAX: u32>(pub(crate) Vec<u8>);
+
+impl<const MAX: u32> Storable for LegacyStored<MAX> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        LegacyStored(bytes.into_owned())
+    }
+}
+
+impl<const MAX: u32> BoundedStorable for LegacyStored<MAX> {
+    const MAX_SIZE: u32 = MAX;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Moves every proposal out of the map in `from` into `to`. Proposal maps were
+// created with a value size of 100 until it grew to MAX_VALUE_SIZE.
+pub(crate) fn migrate_proposals(from: MemoryId, to: &mut StableBTreeMap<u64, Proposal, Memory>) {
+    let memory = MEMORY_MANAGER.with(|m| m.borrow().get(from));
+    if memory.size() == 0 {
+        return;
+    }
+    let mut header = [0; 12];
+    memory.read(0, &mut header);
+    if &header[0..3] != b"BTR" {
+        return;
+    }
+    match u32::from_le_bytes(header[8..12].try_into().unwrap()) {
+        100 => drain_into::<100>(memory, to),
+        MAX_VALUE_SIZE => drain_into::<MAX_VALUE_SIZE>(memory, to),
+        size => env::trap(&format!("unexpected proposal value size {} in {:?}", size, from)),
+    }
+}
+
+fn drain_into<const MAX: u32>(memory: Memory, to: &mut StableBTreeMap<u64, Proposal, Memory>) {
+    let from: StableBTreeMap<u64, LegacyStored<MAX>, Memory> = StableBTreeMap::load(memory);
+    for (key, bytes) in from.iter() {
+        let previous = READING.with(|r| r.replace(Some(key)));
+        let proposal: Proposal = decode_versioned::<Proposal, LegacyProposal>(&bytes.0);
+        READING.with(|r| r.set(previous));
+        to.insert(key, proposal);
+    }
+    from.clear();
+}
+
+// Moves every weight out of the `u32` map in `from` into `to`. A fixed-size
+// value cannot grow in place, so widened weight maps live in new memories.
+pub(crate) fn migrate_weights<K: BoundedStorable + Ord + Clone>(
+    from: MemoryId,
+    to: &mut StableBTreeMap<K, u64, Memory>,
+) {
+    let memory = MEMORY_MANAGER.with(|m| m.borrow().get(from));
+    if memory.size() == 0 {
+        return;
+    }
+    let from: StableBTreeMap<K, u32, Memory> = StableBTreeMap::init(memory);
+    for (key, weight) in from.iter() {
+        to.insert(key, weight.into());
+    }
+    from.clear();
+}
@@ -1,199 +1,4644 @@
 use candid::{CandidType, Decode, Deserialize, Encode};
-use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{BoundedStorable, DefaultMemoryImpl, StableBTreeMap, Storable};
-use std::{borrow::Cow, cell::RefCell};
+use ic_stable_structures::memory_manager::{MemoryManager, VirtualMemory};
+use ic_stable_structures::{
+    BoundedStorable, DefaultMemoryImpl, StableBTreeMap, StableCell, Storable,
+};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, cell::{Cell, RefCell}, collections::{BTreeSet, HashMap, HashSet}};
+
+mod env;
+mod legacy;
+mod memory;
+mod tally_tree;
+#[cfg(test)]
+mod tests;
 
 #[ic_cdk::query]
 fn greet(name: String) -> String {
     format!("Hello, {}!", name)
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Clone)]
 struct Proposal {
     description: String,
-    approve: u32,
-    reject: u32,
-    pass: u32,
+    approve: u64,
+    reject: u64,
+    pass: u64,
     is_active: bool,
     voted: Vec<candid::Principal>,
     owner: candid::Principal,
+    private_voters: bool,
+    // Approve/Reject only; Pass votes are refused.
+    binary: bool,
+    // Quorum as a percentage of the eligible voters instead of a fixed count.
+    quorum_pct: Option<u8>,
+    created_at: u64,
+    updated_at: u64,
+    // When false the owner's own vote is recorded but left out of the outcome.
+    owner_vote_counts: bool,
+    // Votes count with the voter's weight as snapshotted at creation.
+    weighted: bool,
+    tags: Vec<String>,
+    // SHA-256 of a shared secret voters must present; never returned to clients.
+    secret_hash: Option<[u8; 32]>,
+    attachments: Vec<Attachment>,
+    // Readable alias such as "GOV-007", minted by `create_proposal_auto`.
+    human_id: Option<String>,
+    // When each counter last changed, i.e. first reached its current value.
+    reached_at: OptionTimes,
+    // Absolute quorum, used when no percentage quorum applies; `None` is QUORUM.
+    quorum: Option<u64>,
+    // Share of the counted votes an option needs to win; `None` is 50.
+    threshold_pct: Option<u8>,
+    // Votes the winning option needs at the least, whatever its share.
+    min_winning_votes: Option<u64>,
+    // Unlisted proposals are only reachable by key, e.g. polls shared by link.
+    listed: bool,
+    // No more edits once the first vote is in, so voters agreed to what they see.
+    lock_on_first_vote: bool,
+    // Call made by `execute_proposal` once the proposal is approved.
+    execution: Option<ExecutionPayload>,
+    executed: bool,
+    // Votes only through `vote_private`, which keeps no voter list.
+    private_tally: bool,
+    // Ballots cast through `vote_private`; their voters are not in `voted`.
+    anonymous_ballots: u32,
+    // No votes are accepted from this time on; the expiry sweep then ends the
+    // proposal as its owner would.
+    deadline: Option<u64>,
+    // Live proposals sharing a group take one vote per principal between them.
+    vote_group_id: Option<u64>,
+}
+
+// Inter-canister call with Candid-encoded `args`.
+#[derive(CandidType, Deserialize, Clone)]
+struct ExecutionPayload {
+    target: candid::Principal,
+    method: String,
+    args: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Default)]
+struct OptionTimes {
+    approve: u64,
+    reject: u64,
+    pass: u64,
+}
+
+// The parts of a proposal covered by `get_proposal_hash`: what was proposed and
+// under which rules, but not its lifecycle state, counters or voters.
+#[derive(CandidType)]
+struct ProposalContent<'a> {
+    description: &'a str,
+    owner: candid::Principal,
+    created_at: u64,
+    private_voters: bool,
+    binary: bool,
+    quorum_pct: Option<u8>,
+    quorum: Option<u64>,
+    threshold_pct: Option<u8>,
+    min_winning_votes: Option<u64>,
+    owner_vote_counts: bool,
+    weighted: bool,
+    tags: &'a [String],
+    attachments: &'a [Attachment],
+    execution: &'a Option<ExecutionPayload>,
+    deadline: Option<u64>,
+    vote_group_id: Option<u64>,
+}
+
+// Off-chain document referenced by the hash of its contents.
+#[derive(CandidType, Deserialize, Clone)]
+struct Attachment {
+    name: String,
+    sha256: [u8; 32],
+    url: String,
+}
+
+impl Proposal {
+    fn tally(&self) -> Tally {
+        Tally {
+            approve: self.approve,
+            reject: self.reject,
+            pass: self.pass,
+        }
+    }
+
+    fn set_tally(&mut self, tally: Tally) {
+        self.approve = tally.approve;
+        self.reject = tally.reject;
+        self.pass = tally.pass;
+    }
+
+    // Like `set_tally`, but also stamps every counter whose value changed.
+    fn set_tally_at(&mut self, tally: Tally, now: u64) {
+        if tally.approve != self.approve {
+            self.reached_at.approve = now;
+        }
+        if tally.reject != self.reject {
+            self.reached_at.reject = now;
+        }
+        if tally.pass != self.pass {
+            self.reached_at.pass = now;
+        }
+        self.set_tally(tally);
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    // Active and still taking votes. Expired proposals stay active until the
+    // next `close_expired_proposals` run, but no read should treat them as open.
+    fn is_open(&self, now: u64) -> bool {
+        self.is_active && !self.is_expired(now)
+    }
+
+    // Voters so far, including those who voted privately.
+    fn voter_count(&self) -> u64 {
+        self.voted.len() as u64 + self.anonymous_ballots as u64
+    }
+
+    // Counters and voters as of `tally_delay_ns` ago, without the voter list if
+    // the proposal keeps its voters private and without counters while
+    // `counts_hidden`.
+    fn public_view(self, key: u64) -> Self {
+        let mut view = lagged_view(key, self);
+        if counts_hidden(&view) {
+            view.set_tally(Tally::default());
+            view.reached_at = OptionTimes::default();
+        }
+        if view.private_voters {
+            view.voted.clear();
+        }
+        view.secret_hash = None;
+        view
+    }
 }
 
 impl Storable for Proposal {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        decode_versioned::<Self, legacy::LegacyProposal>(bytes.as_ref())
     }
 }
 
-#[derive(CandidType, Deserialize)]
-struct CreateProposal {
-    description: String,
-    is_active: bool,
+// `Storable::from_bytes` cannot fail, so a value that no longer decodes (e.g.
+// after a broken migration) still traps, but with its type, key and the reason.
+fn decode_stored<T>(bytes: &[u8]) -> T
+where
+    T: CandidType + for<'de> Deserialize<'de>,
+{
+    Decode!(bytes, T).unwrap_or_else(|err| corrupt_value::<T>(bytes.len(), &err.to_string()))
+}
+
+// Like `decode_stored`, for types with a `legacy` mirror that still reads values
+// written before fields were added.
+fn decode_versioned<T, L>(bytes: &[u8]) -> T
+where
+    T: CandidType + for<'de> Deserialize<'de>,
+    L: CandidType + for<'de> Deserialize<'de> + Into<T>,
+{
+    match Decode!(bytes, T) {
+        Ok(value) => value,
+        Err(err) => match Decode!(bytes, L) {
+            Ok(legacy) => legacy.into(),
+            Err(_) => corrupt_value::<T>(bytes.len(), &err.to_string()),
+        },
+    }
+}
+
+fn corrupt_value<T>(len: usize, reason: &str) -> ! {
+    let location = match READING.with(Cell::get) {
+        Some(key) => format!(" at key {}", key),
+        None => String::new(),
+    };
+    panic!(
+        "corrupt {}{} in stable memory ({} bytes): {}",
+        std::any::type_name::<T>(),
+        location,
+        len,
+        reason
+    )
+}
+
+// Point read of `map[key]` that names the key if the stored value is corrupt.
+fn read_entry<V: BoundedStorable>(map: &StableBTreeMap<u64, V, Memory>, key: u64) -> Option<V> {
+    let previous = READING.with(|r| r.replace(Some(key)));
+    let value = map.get(&key);
+    READING.with(|r| r.set(previous));
+    value
+}
+
+// Principals are at most 29 bytes, which lets them be used inside map keys.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct StorablePrincipal(candid::Principal);
+
+// Required by the tuple `Storable` impl; the value itself is never observed.
+impl Default for StorablePrincipal {
+    fn default() -> Self {
+        StorablePrincipal(candid::Principal::anonymous())
+    }
+}
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        StorablePrincipal(candid::Principal::from_slice(bytes.as_ref()))
+    }
+}
+
+impl BoundedStorable for StorablePrincipal {
+    const MAX_SIZE: u32 = 29;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// A UTF-8 string of at most `MAX` bytes; callers validate the length before storing.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct StorableString<const MAX: u32>(String);
+
+impl<const MAX: u32> Storable for StorableString<MAX> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        let len = bytes.len();
+        let string = String::from_utf8(bytes.into_owned())
+            .unwrap_or_else(|err| corrupt_value::<Self>(len, &err.to_string()));
+        StorableString(string)
+    }
+}
+
+impl<const MAX: u32> BoundedStorable for StorableString<MAX> {
+    const MAX_SIZE: u32 = MAX;
+    const IS_FIXED_SIZE: bool = false;
 }
 
+// How an individual principal voted on a proposal. `choice` and `timestamp`
+// describe the latest ballot when the principal may cast several.
 #[derive(CandidType, Deserialize)]
-enum VoteTypes {
-    Approve,
-    Reject,
-    Pass,
+struct VoteRecord {
+    choice: VoteTypes,
+    timestamp: u64,
+    // Everything the principal's ballots added to the counters.
+    cast: Tally,
+    ballots: u32,
+}
+
+impl Storable for VoteRecord {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_versioned::<Self, legacy::LegacyVoteRecord>(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for VoteRecord {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 #[derive(CandidType, Deserialize)]
-enum VoteError {
-    AlreadyVoted,
-    ProposalNotActive,
-    Unauthorized,
-    NoProposal,
-    UpdateError,
-    VoteFailed,
+struct Voter {
+    registered_at: u64,
+    display_name: String,
 }
 
-const MAX_VALUE_SIZE: u32 = 100;
-impl BoundedStorable for Proposal {
-    const MAX_SIZE: u32 = MAX_VALUE_SIZE;
+impl Storable for Voter {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_stored(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Voter {
+    const MAX_SIZE: u32 = 128;
     const IS_FIXED_SIZE: bool = false;
 }
 
-type Memory = VirtualMemory<DefaultMemoryImpl>;
-thread_local! {
-    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
-        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
-    static PROPOSAL_MAP: RefCell<StableBTreeMap<u64, Proposal, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
-        )
-    );
-    static PARTICIPATION_PERCENTAGE_MAP: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
-        )
-    );
+// Liquid democracy: the delegate's vote also counts for the delegator,
+// unless the delegator votes directly or the delegation has expired.
+#[derive(CandidType, Deserialize)]
+struct Delegation {
+    to: candid::Principal,
+    expiry_ns: Option<u64>,
 }
 
+impl Delegation {
+    fn is_live(&self, now: u64) -> bool {
+        self.expiry_ns.is_none_or(|expiry| now < expiry)
+    }
+}
 
-#[ic_cdk_macros::query]
-fn get_proposal(key: u64) -> Option<Proposal> {
-    PROPOSAL_MAP.with(|p| p.borrow().get(&key))
+impl Storable for Delegation {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_stored(bytes.as_ref())
+    }
 }
-#[ic_cdk_macros::query]
-fn get_proposal_count() -> u64 {
-    PROPOSAL_MAP.with(|p| p.borrow().len())
+
+impl BoundedStorable for Delegation {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
 }
 
-#[ic_cdk_macros::query]
-fn get_proposal_status(key: u64) -> Option<&'static str> {
-    PROPOSAL_MAP.with(|p| {
-        let proposal = match p.borrow().get(&key) {
-            Some(value) => value,
-            None => return None,
-        };
+// A key claimed through `reserve_key` that only its owner can fill in.
+#[derive(CandidType, Deserialize)]
+struct Reservation {
+    owner: candid::Principal,
+    reserved_at: u64,
+}
 
-        if proposal.voted.len() < 5 {
-            // The proposal does not have enough votes for evaluation.
-            return Some("Undecided");
-        }
+impl Reservation {
+    fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.reserved_at) > RESERVATION_TTL_NS
+    }
+}
 
-        let total_votes = proposal.approve + proposal.reject + proposal.pass;
-        let approval_percentage = (proposal.approve as f64 / total_votes as f64) * 100.0;
-        let rejection_percentage = (proposal.reject as f64 / total_votes as f64) * 100.0;
-        let pass_percentage = (proposal.pass as f64 / total_votes as f64) * 100.0;
+impl Storable for Reservation {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_stored(bytes.as_ref())
+    }
+}
 
-        if approval_percentage >= 50.0 {
-            Some("Approved")
-        } else if rejection_percentage >= 50.0 {
-            Some("Rejected")
-        } else if pass_percentage >= 50.0 {
-            Some("Passed")
-        } else {
-            Some("Undecided")
-        }
-    })
+impl BoundedStorable for Reservation {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
 }
 
+// A ballot waiting for `confirm_vote`.
+#[derive(CandidType, Deserialize)]
+struct PendingVote {
+    voter: candid::Principal,
+    proposal_key: u64,
+    choice: VoteTypes,
+    expires_at: u64,
+}
 
+impl Storable for PendingVote {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_stored(bytes.as_ref())
+    }
+}
 
-#[ic_cdk_macros::update]
-fn create_proposal(key: u64, proposal: CreateProposal) -> Option<Proposal> {    let value = Proposal {
-        description: proposal.description,
-        approve: 0u32,
-        reject: 0u32,
-        pass: 0u32,
-        is_active: proposal.is_active,
-        voted: vec![],
-        owner: ic_cdk::caller(),
-    };
-    PROPOSAL_MAP.with(|p| p.borrow_mut().insert(key, value))
+impl BoundedStorable for PendingVote {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
 }
 
+type VoteToken = [u8; 32];
 
-#[ic_cdk_macros::update]
-fn edit_proposal(key: u64, proposal: CreateProposal) -> Result<(), VoteError> {
-    PROPOSAL_MAP.with(|p| {
-        let old_proposal = match p.borrow().get(&key) {
-            Some(value) => value,
-            None => return Err(VoteError::NoProposal),
-        };
-        if ic_cdk::caller() != old_proposal.owner {            return Err(VoteError::Unauthorized);
-        }
-        let value = Proposal {
-            description: proposal.description,
-            approve: old_proposal.approve,
-            reject: old_proposal.reject,
-            pass: old_proposal.pass,
-            is_active: proposal.is_active,
-            voted: old_proposal.voted,
-            owner: ic_cdk::caller(),
-        };
-        let res = p.borrow_mut().insert(key, value);
-        match res {
-            Some(_) => Ok(()),
-            None => Err(VoteError::UpdateError),
+// Counters of a proposal at one point in time, kept for delayed reporting.
+#[derive(CandidType, Deserialize, Clone, Copy, Default)]
+struct TallySnapshot {
+    tally: Tally,
+    // Voters who are named; private-tally ballots are counted separately.
+    voters: u32,
+    // `None` in snapshots taken before private-tally ballots were recorded.
+    anonymous_ballots: Option<u32>,
+}
+
+impl TallySnapshot {
+    fn of(proposal: &Proposal) -> Self {
+        TallySnapshot {
+            tally: proposal.tally(),
+            voters: proposal.voted.len() as u32,
+            anonymous_ballots: Some(proposal.anonymous_ballots),
         }
-    })
+    }
+
+    fn voter_count(&self) -> u64 {
+        self.voters as u64 + self.anonymous_ballots.unwrap_or(0) as u64
+    }
+}
+
+impl Storable for TallySnapshot {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_versioned::<Self, legacy::LegacyTallySnapshot>(bytes.as_ref())
+    }
 }
 
+impl BoundedStorable for TallySnapshot {
+    const MAX_SIZE: u32 = 96;
+    const IS_FIXED_SIZE: bool = false;
+}
 
-#[ic_cdk_macros::update]
-fn end_proposal(key: u64) -> Result<(), VoteError> {
-    PROPOSAL_MAP.with(|p| {
-        let mut proposal = p.borrow_mut().get(&key).unwrap();
-        if ic_cdk::caller() != proposal.owner {
-            return Err(VoteError::Unauthorized);
-        }
-        proposal.is_active = false;
-        let res = p.borrow_mut().insert(key, proposal);
-        match res {
-            Some(_) => Ok(()),
-            None => Err(VoteError::UpdateError),
-        }
-    })
+// Audit trail entry; the log is append-only and survives purges.
+#[derive(CandidType, Deserialize)]
+struct Event {
+    proposal_key: u64,
+    caller: candid::Principal,
+    timestamp: u64,
+    kind: EventKind,
+    // Whether a ballot's voter was private when it was cast, since the proposal
+    // may be gone or replaced by the time the event is read. `None` for other
+    // events and for ballots logged before this was recorded.
+    private_voters: Option<bool>,
 }
 
+#[derive(CandidType, Deserialize)]
+enum EventKind {
+    Created { is_active: bool },
+    Edited { is_active: bool },
+    Voted { choice: VoteTypes, weight: u64 },
+    VoteCancelled,
+    // All votes were dropped because the description changed.
+    VotesInvalidated,
+    Ended,
+    Activated,
+}
 
-#[ic_cdk_macros::update]
-fn vote(key: u64, choice: VoteTypes) -> Result<(), VoteError> {
-    PROPOSAL_MAP.with(|p| {
-        let mut proposal = p.borrow_mut().get(&key).unwrap();
-        let caller = ic_cdk::caller();
-        if proposal.voted.contains(&caller) {
-            return Err(VoteError::AlreadyVoted);
-        } else if !proposal.is_active {
-            return Err(VoteError::ProposalNotActive);
+impl Event {
+    // `None` for ballots a client may not see at all; the others lose their
+    // caller if the voter was private.
+    fn public_view(mut self, view: BallotView) -> Option<Self> {
+        if !matches!(self.kind, EventKind::Voted { .. } | EventKind::VoteCancelled) {
+            return Some(self);
         }
-        match choice {
-            VoteTypes::Approve => proposal.approve += 1,
-            VoteTypes::Reject => proposal.reject += 1,
-            VoteTypes::Pass => proposal.pass += 1,
+        if view.counts_hidden || self.timestamp > view.cutoff {
+            return None;
         }
-        proposal.voted.push(caller);
-        let res = p.borrow_mut().insert(key, proposal);
-        match res {
-            Some(_) => Ok(()),
-            None => Err(VoteError::VoteFailed),
+        if !view.audit && self.private_voters.unwrap_or(view.private_voters) {
+            self.caller = candid::Principal::anonymous();
         }
-    })
+        Some(self)
+    }
+}
+
+// What a client may learn from the ballot events of one proposal.
+#[derive(Clone, Copy)]
+struct BallotView {
+    // Admins see every ballot with its voter.
+    audit: bool,
+    // While counts are hidden, ballots would give them away one by one.
+    counts_hidden: bool,
+    // For ballots that do not say whether their voter was private.
+    private_voters: bool,
+    // Ballots after `tally_cutoff` are not in the lagged counters either.
+    cutoff: u64,
+}
+
+impl Storable for Event {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_versioned::<Self, legacy::LegacyEvent>(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Event {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// The last idempotent `vote` of a principal, replayed when the key repeats.
+#[derive(CandidType, Deserialize)]
+struct IdempotentVote {
+    key_hash: [u8; 32],
+    result: Result<(), VoteError>,
+}
+
+impl Storable for IdempotentVote {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_versioned::<Self, legacy::LegacyIdempotentVote>(bytes.as_ref())
+    }
+}
+
+// The Candid type table lists every `VoteError` variant, so leave room to grow.
+impl BoundedStorable for IdempotentVote {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// One stored version of a proposal's description.
+#[derive(CandidType, Deserialize)]
+struct Revision {
+    description: String,
+    timestamp: u64,
+}
+
+impl Storable for Revision {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_stored(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Revision {
+    const MAX_SIZE: u32 = MAX_VALUE_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Keys of live proposals breaking a storage invariant, from `verify_integrity`.
+#[derive(CandidType, Default)]
+struct IntegrityReport {
+    scanned: u64,
+    // Unweighted proposals whose counters disagree with the ballots cast.
+    tally_mismatch: Vec<u64>,
+    // Proposals whose encoding no longer fits into a PROPOSAL_MAP slot.
+    oversized: Vec<u64>,
+    // Participation entries without a live or archived proposal.
+    orphaned_participation: Vec<u64>,
+}
+
+#[derive(CandidType)]
+enum DiffChunk {
+    Unchanged(String),
+    Added(String),
+    Removed(String),
+}
+
+// One cast ballot, in the order ballots arrived.
+#[derive(CandidType, Deserialize)]
+struct TimelineEntry {
+    timestamp: u64,
+    choice: VoteTypes,
+    weight: u64,
+}
+
+impl Storable for TimelineEntry {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_versioned::<Self, legacy::LegacyTimelineEntry>(bytes.as_ref())
+    }
 }
 
+impl BoundedStorable for TimelineEntry {
+    const MAX_SIZE: u32 = 96;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(CandidType, Deserialize)]
+struct Comment {
+    author: candid::Principal,
+    body: String,
+    timestamp: u64,
+}
+
+impl Storable for Comment {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_stored(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Comment {
+    const MAX_SIZE: u32 = MAX_COMMENT_LEN + 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Who may comment, judged by whether the caller voted on the proposal.
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq)]
+enum CommentPolicy {
+    Open,
+    VotersOnly,
+    NonVotersOnly,
+}
+
+#[derive(CandidType, Deserialize)]
+struct VoterProfile {
+    display_name: String,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+struct CanisterConfig {
+    admin: candid::Principal,
+    // Spam policy for descriptions; `None` disables the respective check.
+    min_description_distinct_chars: Option<u32>,
+    min_description_words: Option<u32>,
+    // Canister notified with `proposal_finalized(key, status)` when a proposal ends.
+    subscriber: Option<candid::Principal>,
+    // How long after voting a caller may still retract the vote.
+    vote_cancellation_window_ns: u64,
+    // Refuse new proposals whose normalized description matches an active one.
+    reject_duplicate_descriptions: bool,
+    // Refuse new proposals repeating one of the caller's own live descriptions.
+    owner_unique_descriptions: bool,
+    // Full scans are refused while the cycle balance is below this floor.
+    min_scan_balance: Option<u64>,
+    // Only principals that called `register_voter` may vote.
+    require_registration: bool,
+    max_active_proposals: Option<u64>,
+    // Ballots each principal may cast per proposal, spread over any options.
+    votes_per_principal: u32,
+    // Tallies and outcomes are reported only once this many principals voted.
+    min_reveal_voters: u32,
+    // Require more than the winning threshold instead of at least reaching it.
+    strict_majority: bool,
+    // Public tallies and statuses report the state as of this long ago.
+    tally_delay_ns: u64,
+    // How long past tallies stay available to `get_tally_as_of`.
+    tally_history_retention_ns: u64,
+    // Let the anonymous principal create proposals and vote.
+    allow_anonymous: bool,
+    // A description change drops every vote cast so far.
+    invalidate_votes_on_edit: bool,
+    // When set, proposals can only be created through `create_proposal_pow`.
+    pow_difficulty: Option<u8>,
+    // Emergency stop: user updates fail while queries and admin calls keep working.
+    frozen: bool,
+    // Percentage quorum for new proposals that do not choose one themselves.
+    default_quorum_pct: Option<u8>,
+    tie_break: TieBreak,
+    // Approve votes required for Approved in addition to the majority.
+    min_approve_count: Option<u32>,
+    comment_policy: CommentPolicy,
+    // Close a proposal as soon as a vote makes it Approved.
+    auto_finalize: bool,
+    // Keep every ballot with its time for turnout-over-time analytics.
+    record_vote_timeline: bool,
+    // Principals first seen less than this long ago cannot vote yet.
+    min_account_age_ns: u64,
+    // Canister answering `get_voting_power: (principal) -> (nat64)`; when set it
+    // replaces the weight snapshot on weighted proposals.
+    neuron_source: Option<candid::Principal>,
+    // Refuse ballots once the remaining eligible voters can no longer change the outcome.
+    early_lock: bool,
+    // Cap on distinct tags across all proposals; reusing a known tag is always fine.
+    max_distinct_tags: Option<u64>,
+    // Vote and cancel as the base identity registered for the caller, if any.
+    normalize_principals: bool,
+    // How long a `prepare_vote` token can still be confirmed.
+    vote_confirmation_window_ns: u64,
+    // When false only voters who approved or rejected count towards quorum.
+    pass_is_turnout: bool,
+    // Description revisions kept per proposal; older ones are dropped first.
+    max_description_revisions: u32,
+    // Deadline given to proposals created without one.
+    default_ttl_ns: Option<u64>,
+    // Minimum time between two proposals created by the same principal.
+    min_create_interval_ns: u64,
+}
+
+impl Default for CanisterConfig {
+    fn default() -> Self {
+        CanisterConfig {
+            admin: candid::Principal::anonymous(),
+            min_description_distinct_chars: None,
+            min_description_words: None,
+            subscriber: None,
+            vote_cancellation_window_ns: 30_000_000_000,
+            reject_duplicate_descriptions: false,
+            owner_unique_descriptions: false,
+            min_scan_balance: None,
+            require_registration: false,
+            max_active_proposals: None,
+            votes_per_principal: 1,
+            min_reveal_voters: 0,
+            strict_majority: false,
+            tally_delay_ns: 0,
+            tally_history_retention_ns: 0,
+            allow_anonymous: false,
+            invalidate_votes_on_edit: false,
+            pow_difficulty: None,
+            frozen: false,
+            default_quorum_pct: None,
+            tie_break: TieBreak::None,
+            min_approve_count: None,
+            comment_policy: CommentPolicy::Open,
+            auto_finalize: false,
+            record_vote_timeline: false,
+            min_account_age_ns: 0,
+            neuron_source: None,
+            early_lock: false,
+            max_distinct_tags: None,
+            normalize_principals: false,
+            vote_confirmation_window_ns: 300_000_000_000,
+            pass_is_turnout: true,
+            max_description_revisions: 20,
+            default_ttl_ns: None,
+            min_create_interval_ns: 0,
+        }
+    }
+}
+
+impl Storable for CanisterConfig {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        decode_versioned::<Self, legacy::LegacyConfig>(bytes.as_ref())
+    }
+}
+
+#[derive(CandidType, Deserialize, Default)]
+struct CreateProposal {
+    description: String,
+    is_active: bool,
+    // Optional settings: defaulted on create, left unchanged on edit when `None`.
+    private_voters: Option<bool>,
+    binary: Option<bool>,
+    quorum_pct: Option<u8>,
+    owner_vote_counts: Option<bool>,
+    // Fixed at creation together with the weight snapshot; ignored on edit.
+    weighted: Option<bool>,
+    tags: Option<Vec<String>>,
+    // Plain secret that gates voting; an empty string removes it on edit.
+    secret: Option<String>,
+    attachments: Option<Vec<Attachment>>,
+    listed: Option<bool>,
+    lock_on_first_vote: Option<bool>,
+    execution: Option<ExecutionPayload>,
+    // Fixed at creation; ignored on edit.
+    private_tally: Option<bool>,
+    // Defaults to `default_ttl_ns` after creation, if that is set.
+    deadline: Option<u64>,
+    // Fixed at creation; ignored on edit.
+    vote_group_id: Option<u64>,
+}
+
+// Predicates for `list_proposals_filtered`; unset fields match everything.
+#[derive(CandidType, Deserialize)]
+struct ProposalFilter {
+    owner: Option<candid::Principal>,
+    active_only: bool,
+    tag: Option<String>,
+    min_votes: Option<u32>,
+}
+
+impl ProposalFilter {
+    fn matches(&self, proposal: &Proposal) -> bool {
+        self.owner.is_none_or(|owner| proposal.owner == owner)
+            && (!self.active_only || proposal.is_open(env::time()))
+            && self.tag.as_ref().is_none_or(|tag| {
+                proposal.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+            })
+            && self
+                .min_votes
+                .is_none_or(|min| proposal.voter_count() >= min as u64)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq, Debug)]
+enum VoteTypes {
+    Approve,
+    Reject,
+    Pass,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+struct Tally {
+    approve: u64,
+    reject: u64,
+    pass: u64,
+}
+
+impl Tally {
+    fn add(&mut self, choice: VoteTypes, weight: u64) {
+        match choice {
+            VoteTypes::Approve => self.approve = self.approve.saturating_add(weight),
+            VoteTypes::Reject => self.reject = self.reject.saturating_add(weight),
+            VoteTypes::Pass => self.pass = self.pass.saturating_add(weight),
+        }
+    }
+
+    fn remove(&mut self, choice: VoteTypes, weight: u64) {
+        match choice {
+            VoteTypes::Approve => self.approve = self.approve.saturating_sub(weight),
+            VoteTypes::Reject => self.reject = self.reject.saturating_sub(weight),
+            VoteTypes::Pass => self.pass = self.pass.saturating_sub(weight),
+        }
+    }
+
+    fn subtract(&mut self, other: &Tally) {
+        self.remove(VoteTypes::Approve, other.approve);
+        self.remove(VoteTypes::Reject, other.reject);
+        self.remove(VoteTypes::Pass, other.pass);
+    }
+
+    // Votes that take part in the outcome; Pass is left out of binary proposals.
+    fn counted(&self, binary: bool) -> u64 {
+        let counted = self.approve.saturating_add(self.reject);
+        if binary {
+            counted
+        } else {
+            counted.saturating_add(self.pass)
+        }
+    }
+
+    fn get(&self, choice: VoteTypes) -> u64 {
+        match choice {
+            VoteTypes::Approve => self.approve,
+            VoteTypes::Reject => self.reject,
+            VoteTypes::Pass => self.pass,
+        }
+    }
+}
+
+// Returned by `vote_with_receipt` so clients see the new tally without a second call.
+#[derive(CandidType, Deserialize)]
+struct VoteReceipt {
+    proposal_key: u64,
+    choice: VoteTypes,
+    tally_after: Tally,
+    timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Metrics {
+    approval_pct: f64,
+    rejection_pct: f64,
+    pass_pct: f64,
+    turnout: u32,
+    quorum_met: bool,
+}
+
+// How a quorate proposal whose leading options are tied is resolved.
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq)]
+enum TieBreak {
+    // The majority thresholds alone decide, so ties may stay undecided.
+    None,
+    // The tied option that reached its count first wins.
+    EarliestMajority,
+}
+
+#[derive(CandidType, Deserialize, Default)]
+struct StatusCounts {
+    undecided: u64,
+    approved: u64,
+    rejected: u64,
+    passed: u64,
+}
+
+#[derive(CandidType, Deserialize, Default)]
+struct OwnerStats {
+    total_created: u64,
+    total_approved: u64,
+    total_rejected: u64,
+    // Voters per proposal; zero for an owner without proposals.
+    avg_turnout: f64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq, Debug)]
+enum ProposalStatus {
+    Undecided,
+    Approved,
+    Rejected,
+    Passed,
+}
+
+impl ProposalStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            ProposalStatus::Undecided => "Undecided",
+            ProposalStatus::Approved => "Approved",
+            ProposalStatus::Rejected => "Rejected",
+            ProposalStatus::Passed => "Passed",
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+enum VoteError {
+    AlreadyVoted,
+    ProposalNotActive,
+    Unauthorized,
+    NoProposal,
+    UpdateError,
+    VoteFailed,
+    InvalidDescription,
+    ProposalActive,
+    NotVoted,
+    CancellationWindowClosed,
+    InvalidDisplayName,
+    InvalidOption,
+    NotEligible,
+    InvalidQuorum,
+    DuplicateProposal,
+    ProposalAlreadyExists,
+    InsufficientCycles,
+    NotRegistered,
+    ActiveLimitReached,
+    InvalidDelegation,
+    NoVotingPower,
+    AllowanceExhausted,
+    InvalidTag,
+    InvalidAttachment,
+    InvalidHumanId,
+    InvalidProofOfWork,
+    CanisterFrozen,
+    ProposalTooLarge { size: u32, max: u32 },
+    InvalidThreshold,
+    InvalidComment,
+    AccountTooNew,
+    NoReservation,
+    ResultLocked,
+    ProposalLocked,
+    TagLimitReached,
+    InvalidVoteToken,
+    VoteTokenExpired,
+    NotApproved,
+    AlreadyExecuted,
+    InvalidExecution,
+    ExecutionFailed,
+    WrongVotingMode,
+    VoteInProgress,
+    RateLimited,
+}
+
+// Large enough for a proposal with a couple hundred voters.
+const MAX_VALUE_SIZE: u32 = 8192;
+const MAX_PAGE_SIZE: u64 = 100;
+const MAX_BATCH_SIZE: usize = 50;
+const MAX_ELIGIBLE_BATCH_SIZE: usize = 500;
+const MAX_DISPLAY_NAME_LEN: u32 = 32;
+const MAX_COMMENT_LEN: u32 = 512;
+// Ballots beyond this are still counted but no longer added to the timeline.
+const MAX_TIMELINE_LEN: u64 = 10_000;
+const MAX_TAGS: usize = 8;
+const MAX_TAG_LEN: usize = 32;
+type TagKey = StorableString<{ MAX_TAG_LEN as u32 }>;
+const MAX_HUMAN_ID_PREFIX_LEN: u32 = 16;
+// Prefix, dash and a u64 counter.
+const MAX_HUMAN_ID_LEN: u32 = MAX_HUMAN_ID_PREFIX_LEN + 21;
+// Keeps a fully attached proposal well inside MAX_VALUE_SIZE.
+const MAX_ATTACHMENTS: usize = 4;
+const MAX_ATTACHMENT_NAME_LEN: usize = 64;
+const MAX_ATTACHMENT_URL_LEN: usize = 256;
+const MAX_EXECUTION_METHOD_LEN: usize = 64;
+const MAX_EXECUTION_ARGS_LEN: usize = 2048;
+// Minimum number of votes before a proposal can be decided, unless it sets its own.
+const QUORUM: u64 = 5;
+// Reservations that were not finalized within a day can no longer be and are swept.
+const RESERVATION_TTL_NS: u64 = 24 * 60 * 60 * 1_000_000_000;
+// How late after its deadline an expired proposal is closed at the most.
+const EXPIRY_SWEEP_INTERVAL_NS: u64 = 60 * 1_000_000_000;
+
+impl BoundedStorable for Proposal {
+    const MAX_SIZE: u32 = MAX_VALUE_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+thread_local! {
+    // Key of the entry `read_entry` is decoding, for trap messages.
+    static READING: Cell<Option<u64>> = const { Cell::new(None) };
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    static PROPOSAL_MAP: RefCell<StableBTreeMap<u64, Proposal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::PROPOSAL_MAP)),
+        )
+    );
+    static PARTICIPATION_PERCENTAGE_MAP: RefCell<StableBTreeMap<u64, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::PARTICIPATION_PERCENTAGE_MAP)),
+        )
+    );
+    static CONFIG: RefCell<StableCell<CanisterConfig, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::CONFIG)),
+            CanisterConfig::default(),
+        ).expect("failed to initialize the config cell")
+    );
+    // Heap-only: rebuilt from PROPOSAL_MAP on demand after an upgrade.
+    static STATUS_CACHE: RefCell<HashMap<u64, ProposalStatus>> = RefCell::new(HashMap::new());
+    // (deadline, key) of every active proposal with a deadline, so the expiry
+    // sweep only reads the proposals that are due. Heap-only: rebuilt in `post_upgrade`.
+    static DEADLINES: RefCell<BTreeSet<(u64, u64)>> = const { RefCell::new(BTreeSet::new()) };
+    // (owner, group, key) of every live proposal in a vote group. Heap-only:
+    // rebuilt in `post_upgrade`.
+    static VOTE_GROUPS: RefCell<BTreeSet<(candid::Principal, u64, u64)>> =
+        const { RefCell::new(BTreeSet::new()) };
+    // (voter, key) pairs with an async vote waiting on an inter-canister call.
+    static VOTES_IN_FLIGHT: RefCell<HashSet<(candid::Principal, u64)>> = RefCell::new(HashSet::new());
+    // Closed proposals moved out of PROPOSAL_MAP so that listings stay cheap.
+    static ARCHIVE_MAP: RefCell<StableBTreeMap<u64, Proposal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::ARCHIVE_MAP)),
+        )
+    );
+    static VOTE_MAP: RefCell<StableBTreeMap<(u64, StorablePrincipal), VoteRecord, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::VOTE_MAP)),
+        )
+    );
+    static DISPLAY_NAME_MAP: RefCell<StableBTreeMap<StorablePrincipal, StorableString<MAX_DISPLAY_NAME_LEN>, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::DISPLAY_NAME_MAP)),
+        )
+    );
+    // Per-proposal voter whitelist; a proposal without entries is open to everyone.
+    static ELIGIBLE_MAP: RefCell<StableBTreeMap<(u64, StorablePrincipal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::ELIGIBLE_MAP)),
+        )
+    );
+    // (hash of normalized description, key) for every active proposal.
+    static DESCRIPTION_INDEX: RefCell<StableBTreeMap<([u8; 32], u64), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::DESCRIPTION_INDEX)),
+        )
+    );
+    static VOTER_MAP: RefCell<StableBTreeMap<StorablePrincipal, Voter, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::VOTER_MAP)),
+        )
+    );
+    // Number of active proposals in PROPOSAL_MAP, kept in step by every update.
+    static ACTIVE_COUNT: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::ACTIVE_COUNT)),
+            0,
+        ).expect("failed to initialize the active count")
+    );
+    static DELEGATION_MAP: RefCell<StableBTreeMap<StorablePrincipal, Delegation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::DELEGATION_MAP)),
+        )
+    );
+    // Current stake of each principal, maintained by the admin.
+    static VOTER_WEIGHTS: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::VOTER_WEIGHTS)),
+        )
+    );
+    // VOTER_WEIGHTS as it was when each weighted proposal was created.
+    static WEIGHT_SNAPSHOT: RefCell<StableBTreeMap<(u64, StorablePrincipal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::WEIGHT_SNAPSHOT)),
+        )
+    );
+    // Principals interested in a proposal, for external indexers; nothing is pushed.
+    static SUBSCRIPTION_MAP: RefCell<StableBTreeMap<(u64, StorablePrincipal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::SUBSCRIPTION_MAP)),
+        )
+    );
+    // (key, time) -> counters after each tally change, pruned to the delay window.
+    static TALLY_HISTORY: RefCell<StableBTreeMap<(u64, u64), TallySnapshot, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::TALLY_HISTORY)),
+        )
+    );
+    static HUMAN_ID_MAP: RefCell<StableBTreeMap<StorableString<MAX_HUMAN_ID_LEN>, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::HUMAN_ID_MAP)),
+        )
+    );
+    // Last number minted per prefix; never reused, even after purges.
+    static HUMAN_ID_COUNTERS: RefCell<StableBTreeMap<StorableString<MAX_HUMAN_ID_PREFIX_LEN>, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::HUMAN_ID_COUNTERS)),
+        )
+    );
+    // Sequence number -> event, in the order the events happened.
+    static EVENT_LOG: RefCell<StableBTreeMap<u64, Event, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::EVENT_LOG)),
+        )
+    );
+    // Reverse of DELEGATION_MAP: (delegate, delegator).
+    static DELEGATOR_INDEX: RefCell<StableBTreeMap<(StorablePrincipal, StorablePrincipal), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::DELEGATOR_INDEX)),
+        )
+    );
+    // One entry per principal: retries only ever repeat the latest call.
+    static IDEMPOTENCY_MAP: RefCell<StableBTreeMap<StorablePrincipal, IdempotentVote, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::IDEMPOTENCY_MAP)),
+        )
+    );
+    // (key, sequence number within the proposal) -> comment.
+    static COMMENT_MAP: RefCell<StableBTreeMap<(u64, u64), Comment, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::COMMENT_MAP)),
+        )
+    );
+    // (key, ballot sequence number) -> ballot, while `record_vote_timeline` is on.
+    static VOTE_TIMELINE: RefCell<StableBTreeMap<(u64, u64), TimelineEntry, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::VOTE_TIMELINE)),
+        )
+    );
+    // (key, revision) -> description; revision 0 is the one the proposal was created with.
+    static DESCRIPTION_HISTORY: RefCell<StableBTreeMap<(u64, u64), Revision, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::DESCRIPTION_HISTORY)),
+        )
+    );
+    // Principal -> time of its first update call that reached the canister state.
+    static FIRST_SEEN: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::FIRST_SEEN)),
+        )
+    );
+    // Keys claimed ahead of creation, until finalized or swept.
+    static RESERVATIONS: RefCell<StableBTreeMap<u64, Reservation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::RESERVATIONS)),
+        )
+    );
+    // (key, voter) -> voting power fetched from `neuron_source` for that proposal.
+    static NEURON_POWER: RefCell<StableBTreeMap<(u64, StorablePrincipal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::NEURON_POWER)),
+        )
+    );
+    // Lowercased tag -> number of live or archived proposals carrying it.
+    static TAG_REGISTRY: RefCell<StableBTreeMap<TagKey, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::TAG_REGISTRY)),
+        )
+    );
+    // (key, round) -> final counters of each round closed by `snapshot_and_reset`.
+    static ROUNDS: RefCell<StableBTreeMap<(u64, u64), TallySnapshot, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::ROUNDS)),
+        )
+    );
+    // Derived principal -> the identity it belongs to, for `normalize_principals`.
+    static PRINCIPAL_ALIASES: RefCell<StableBTreeMap<StorablePrincipal, StorablePrincipal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::PRINCIPAL_ALIASES)),
+        )
+    );
+    // Token -> ballot prepared by `prepare_vote`, until confirmed or expired.
+    static PENDING_VOTES: RefCell<StableBTreeMap<VoteToken, PendingVote, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::PENDING_VOTES)),
+        )
+    );
+    // (key, SHA-256(principal || key)) for every `vote_private` ballot.
+    static NULLIFIERS: RefCell<StableBTreeMap<(u64, [u8; 32]), (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::NULLIFIERS)),
+        )
+    );
+    // Principal -> time it last created a proposal.
+    static LAST_CREATE: RefCell<StableBTreeMap<StorablePrincipal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(memory::LAST_CREATE)),
+        )
+    );
+}
+
+fn config() -> CanisterConfig {
+    CONFIG.with(|c| c.borrow().get().clone())
+}
+
+// Start of the `(key, principal)` entries belonging to one proposal; pair with
+// `take_while` on the key since the management canister is the smallest principal.
+fn proposal_scope(key: u64) -> std::ops::RangeFrom<(u64, StorablePrincipal)> {
+    (key, StorablePrincipal(candid::Principal::management_canister()))..
+}
+
+fn eligible_count(key: u64) -> u64 {
+    ELIGIBLE_MAP.with(|e| {
+        e.borrow()
+            .range(proposal_scope(key))
+            .take_while(|((k, _), _)| *k == key)
+            .count() as u64
+    })
+}
+
+fn is_eligible(key: u64, who: candid::Principal) -> bool {
+    ELIGIBLE_MAP.with(|e| {
+        let e = e.borrow();
+        e.contains_key(&(key, StorablePrincipal(who)))
+            || !matches!(e.range(proposal_scope(key)).next(), Some(((k, _), _)) if k == key)
+    })
+}
+
+fn is_admin(who: &candid::Principal) -> bool {
+    config().admin == *who
+}
+
+fn update_config(f: impl FnOnce(&mut CanisterConfig)) -> Result<(), VoteError> {
+    let mut value = config();
+    if env::caller() != value.admin {
+        return Err(VoteError::Unauthorized);
+    }
+    f(&mut value);
+    CONFIG.with(|c| c.borrow_mut().set(value).map(|_| ()).map_err(|_| VoteError::UpdateError))
+}
+
+fn validate_description(description: &str) -> Result<(), VoteError> {
+    let config = config();
+    if let Some(min) = config.min_description_distinct_chars {
+        let distinct = description
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<HashSet<_>>()
+            .len();
+        if distinct < min as usize {
+            return Err(VoteError::InvalidDescription);
+        }
+    }
+    if let Some(min) = config.min_description_words {
+        if description.split_whitespace().count() < min as usize {
+            return Err(VoteError::InvalidDescription);
+        }
+    }
+    Ok(())
+}
+
+#[ic_cdk_macros::init]
+fn init() {
+    CONFIG.with(|c| {
+        let value = CanisterConfig {
+            admin: env::caller(),
+            ..CanisterConfig::default()
+        };
+        c.borrow_mut().set(value).expect("failed to store the config");
+    });
+    schedule_expiry_sweep();
+}
+
+// Proposals still in the maps of a release whose value size was smaller move to
+// the current maps first. A release from before the config existed leaves it at
+// its defaults, so whoever upgrades such a canister becomes its admin. The
+// active count is rebuilt from the proposals, and certified data, which does
+// not survive upgrades, is set again.
+#[ic_cdk_macros::post_upgrade]
+fn post_upgrade() {
+    PROPOSAL_MAP.with(|p| {
+        legacy::migrate_proposals(memory::LEGACY_PROPOSAL_MAP, &mut p.borrow_mut())
+    });
+    ARCHIVE_MAP.with(|a| legacy::migrate_proposals(memory::LEGACY_ARCHIVE_MAP, &mut a.borrow_mut()));
+    VOTER_WEIGHTS.with(|w| {
+        legacy::migrate_weights(memory::LEGACY_VOTER_WEIGHTS, &mut w.borrow_mut())
+    });
+    WEIGHT_SNAPSHOT.with(|w| {
+        legacy::migrate_weights(memory::LEGACY_WEIGHT_SNAPSHOT, &mut w.borrow_mut())
+    });
+    NEURON_POWER.with(|n| {
+        legacy::migrate_weights(memory::LEGACY_NEURON_POWER, &mut n.borrow_mut())
+    });
+    let mut value = config();
+    if value.admin == candid::Principal::anonymous() {
+        value.admin = env::caller();
+        CONFIG.with(|c| c.borrow_mut().set(value).expect("failed to store the config"));
+    }
+    certify_all_tallies();
+    let active = PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .inspect(|(key, proposal)| index_group(*key, proposal))
+            .filter(|(_, proposal)| proposal.is_active)
+            .inspect(|(key, proposal)| index_deadline(*key, proposal))
+            .count()
+    });
+    ACTIVE_COUNT.with(|c| c.borrow_mut().set(active as u64).expect("failed to store the active count"));
+    schedule_expiry_sweep();
+}
+
+// Certifies the root of `tally_tree`, which has a leaf for the counters of
+// every live listed proposal; unlisted ones are only found by their key and
+// must not be enumerable through the tree either. Clients check a proposal's counters by hashing them up
+// its `get_tally_witness` path and comparing the result with the certified
+// data in `get_certificate`.
+fn certified_leaf(key: u64, proposal: &Proposal) -> Option<tally_tree::Hash> {
+    // Small counters are easily guessed from their hash, so certifying them
+    // would reveal counts that are still hidden. Under a tally delay the
+    // certificate would show the current counters ahead of every other read.
+    if !proposal.listed || counts_hidden(proposal) || config().tally_delay_ns > 0 {
+        return None;
+    }
+    Some(tally_tree::leaf_hash(key, &proposal.tally()))
+}
+
+// Recertifies `key` after its counters changed or it left the live map.
+fn certify_tally(key: u64) {
+    let proposal = PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key));
+    let leaf = proposal.and_then(|proposal| certified_leaf(key, &proposal));
+    env::set_certified_data(&tally_tree::update(key, leaf));
+}
+
+fn certify_all_tallies() {
+    tally_tree::clear();
+    PROPOSAL_MAP.with(|p| {
+        for (key, proposal) in p.borrow().iter() {
+            tally_tree::update(key, certified_leaf(key, &proposal));
+        }
+    });
+    env::set_certified_data(&tally_tree::root());
+}
+
+#[ic_cdk_macros::query]
+fn get_certificate() -> Option<Vec<u8>> {
+    env::data_certificate()
+}
+
+// Certified counters of a proposal and the sibling hashes from their leaf up
+// to the root, lowest first.
+#[derive(CandidType, Deserialize)]
+struct TallyWitness {
+    tally: Tally,
+    siblings: Vec<Vec<u8>>,
+}
+
+#[ic_cdk_macros::query]
+fn get_tally_witness(key: u64) -> Option<TallyWitness> {
+    let proposal = PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key))?;
+    certified_leaf(key, &proposal)?;
+    Some(TallyWitness {
+        tally: proposal.tally(),
+        siblings: tally_tree::witness(key).iter().map(|hash| hash.to_vec()).collect(),
+    })
+}
+
+// Everything here is public; secrets such as proposal passwords live elsewhere.
+#[ic_cdk_macros::query]
+fn get_config() -> CanisterConfig {
+    config()
+}
+
+#[ic_cdk_macros::update]
+fn set_description_policy(
+    min_distinct_chars: Option<u32>,
+    min_words: Option<u32>,
+) -> Result<(), VoteError> {
+    update_config(|c| {
+        c.min_description_distinct_chars = min_distinct_chars;
+        c.min_description_words = min_words;
+    })
+}
+
+
+#[ic_cdk_macros::update]
+fn set_subscriber(subscriber: Option<candid::Principal>) -> Result<(), VoteError> {
+    update_config(|c| c.subscriber = subscriber)
+}
+
+#[ic_cdk_macros::update]
+fn set_vote_cancellation_window(window_ns: u64) -> Result<(), VoteError> {
+    update_config(|c| c.vote_cancellation_window_ns = window_ns)
+}
+
+#[ic_cdk_macros::update]
+fn set_reject_duplicate_descriptions(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.reject_duplicate_descriptions = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_owner_unique_descriptions(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.owner_unique_descriptions = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_min_scan_balance(floor: Option<u64>) -> Result<(), VoteError> {
+    update_config(|c| c.min_scan_balance = floor)
+}
+
+#[ic_cdk_macros::update]
+fn set_require_registration(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.require_registration = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_max_active_proposals(max: Option<u64>) -> Result<(), VoteError> {
+    update_config(|c| c.max_active_proposals = max)
+}
+
+#[ic_cdk_macros::update]
+fn set_votes_per_principal(votes: u32) -> Result<(), VoteError> {
+    if votes == 0 {
+        return Err(VoteError::InvalidOption);
+    }
+    update_config(|c| c.votes_per_principal = votes)
+}
+
+#[ic_cdk_macros::query]
+fn get_voters_by_choice(key: u64, choice: VoteTypes) -> Vec<candid::Principal> {
+    let proposal = PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key));
+    match proposal.map(|proposal| lagged_view(key, proposal)) {
+        Some(proposal) if !proposal.private_voters && !counts_hidden(&proposal) => (),
+        _ => return vec![],
+    }
+    let cutoff = tally_cutoff();
+    VOTE_MAP.with(|v| {
+        v.borrow()
+            .range(proposal_scope(key))
+            .take_while(|((k, _), _)| *k == key)
+            .filter(|(_, record)| record.cast.get(choice) > 0 && record.timestamp <= cutoff)
+            .map(|((_, voter), _)| voter.0)
+            .collect()
+    })
+}
+
+#[ic_cdk_macros::update]
+fn set_min_reveal_voters(voters: u32) -> Result<(), VoteError> {
+    update_config(|c| c.min_reveal_voters = voters)?;
+    certify_all_tallies();
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn set_strict_majority(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.strict_majority = enabled)?;
+    // Cached outcomes were computed under the previous rule.
+    STATUS_CACHE.with(|c| c.borrow_mut().clear());
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn set_tally_delay(delay_ns: u64) -> Result<(), VoteError> {
+    update_config(|c| c.tally_delay_ns = delay_ns)?;
+    certify_all_tallies();
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn set_tally_history_retention(retention_ns: u64) -> Result<(), VoteError> {
+    update_config(|c| c.tally_history_retention_ns = retention_ns)
+}
+
+#[ic_cdk_macros::update]
+fn set_allow_anonymous(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.allow_anonymous = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_invalidate_votes_on_edit(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.invalidate_votes_on_edit = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_pow_difficulty(bits: Option<u8>) -> Result<(), VoteError> {
+    update_config(|c| c.pow_difficulty = bits)
+}
+
+// Applies to proposals created afterwards; existing ones keep their quorum.
+#[ic_cdk_macros::update]
+fn set_default_quorum_pct(quorum_pct: Option<u8>) -> Result<(), VoteError> {
+    validate_quorum(quorum_pct)?;
+    update_config(|c| c.default_quorum_pct = quorum_pct)
+}
+
+#[ic_cdk_macros::update]
+fn set_tie_break(policy: TieBreak) -> Result<(), VoteError> {
+    update_config(|c| c.tie_break = policy)?;
+    STATUS_CACHE.with(|c| c.borrow_mut().clear());
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn set_min_approve_count(min: Option<u32>) -> Result<(), VoteError> {
+    update_config(|c| c.min_approve_count = min)?;
+    STATUS_CACHE.with(|c| c.borrow_mut().clear());
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn set_comment_policy(policy: CommentPolicy) -> Result<(), VoteError> {
+    update_config(|c| c.comment_policy = policy)
+}
+
+#[ic_cdk_macros::update]
+fn set_auto_finalize(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.auto_finalize = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_record_vote_timeline(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.record_vote_timeline = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_min_account_age(age_ns: u64) -> Result<(), VoteError> {
+    update_config(|c| c.min_account_age_ns = age_ns)
+}
+
+#[ic_cdk_macros::update]
+fn set_neuron_source(source: Option<candid::Principal>) -> Result<(), VoteError> {
+    update_config(|c| c.neuron_source = source)
+}
+
+#[ic_cdk_macros::update]
+fn set_early_lock(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.early_lock = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_max_distinct_tags(max: Option<u64>) -> Result<(), VoteError> {
+    update_config(|c| c.max_distinct_tags = max)
+}
+
+#[ic_cdk_macros::update]
+fn set_normalize_principals(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.normalize_principals = enabled)
+}
+
+#[ic_cdk_macros::update]
+fn set_vote_confirmation_window(window_ns: u64) -> Result<(), VoteError> {
+    update_config(|c| c.vote_confirmation_window_ns = window_ns)
+}
+
+#[ic_cdk_macros::update]
+fn set_pass_is_turnout(enabled: bool) -> Result<(), VoteError> {
+    update_config(|c| c.pass_is_turnout = enabled)?;
+    STATUS_CACHE.with(|c| c.borrow_mut().clear());
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn set_max_description_revisions(max: u32) -> Result<(), VoteError> {
+    update_config(|c| c.max_description_revisions = max)
+}
+
+#[ic_cdk_macros::update]
+fn set_default_ttl(ttl_ns: Option<u64>) -> Result<(), VoteError> {
+    update_config(|c| c.default_ttl_ns = ttl_ns)
+}
+
+#[ic_cdk_macros::update]
+fn set_min_create_interval(interval_ns: u64) -> Result<(), VoteError> {
+    update_config(|c| c.min_create_interval_ns = interval_ns)
+}
+
+#[ic_cdk_macros::update]
+fn freeze_canister() -> Result<(), VoteError> {
+    update_config(|c| c.frozen = true)
+}
+
+#[ic_cdk_macros::update]
+fn unfreeze_canister() -> Result<(), VoteError> {
+    update_config(|c| c.frozen = false)
+}
+
+#[ic_cdk_macros::query]
+fn get_remaining_allowance(key: u64, who: candid::Principal) -> u32 {
+    let used = VOTE_MAP
+        .with(|v| v.borrow().get(&(key, StorablePrincipal(who))))
+        .map_or(0, |record| record.ballots);
+    config().votes_per_principal.saturating_sub(used)
+}
+
+#[ic_cdk_macros::query]
+fn get_display_name(who: candid::Principal) -> Option<String> {
+    DISPLAY_NAME_MAP.with(|d| d.borrow().get(&StorablePrincipal(who))).map(|name| name.0)
+}
+
+#[ic_cdk_macros::query]
+fn get_proposal(key: u64) -> Option<Proposal> {
+    PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)).map(|proposal| proposal.public_view(key))
+}
+
+#[ic_cdk_macros::query]
+fn get_owner(key: u64) -> Option<candid::Principal> {
+    PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)).map(|proposal| proposal.owner)
+}
+
+#[ic_cdk_macros::query]
+fn is_owner(key: u64) -> bool {
+    get_owner(key) == Some(env::caller())
+}
+
+#[ic_cdk_macros::query]
+fn get_attachments(key: u64) -> Option<Vec<Attachment>> {
+    PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)).map(|proposal| proposal.attachments)
+}
+
+// Resolves live and archived proposals alike.
+#[ic_cdk_macros::query]
+fn get_by_human_id(human_id: String) -> Option<(u64, Proposal)> {
+    if human_id.len() > MAX_HUMAN_ID_LEN as usize {
+        return None;
+    }
+    let key = HUMAN_ID_MAP.with(|h| h.borrow().get(&StorableString(human_id)))?;
+    PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .or_else(|| ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)))
+        .map(|proposal| (key, proposal.public_view(key)))
+}
+
+// SHA-256 of the Candid encoding of `ProposalContent`; votes and activation
+// changes leave it untouched while any edit of the content changes it.
+#[ic_cdk_macros::query]
+fn get_proposal_hash(key: u64) -> Option<[u8; 32]> {
+    let proposal = PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .or_else(|| ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)))?;
+    let content = ProposalContent {
+        description: &proposal.description,
+        owner: proposal.owner,
+        created_at: proposal.created_at,
+        private_voters: proposal.private_voters,
+        binary: proposal.binary,
+        quorum_pct: proposal.quorum_pct,
+        quorum: proposal.quorum,
+        threshold_pct: proposal.threshold_pct,
+        min_winning_votes: proposal.min_winning_votes,
+        owner_vote_counts: proposal.owner_vote_counts,
+        weighted: proposal.weighted,
+        tags: &proposal.tags,
+        attachments: &proposal.attachments,
+        execution: &proposal.execution,
+        deadline: proposal.deadline,
+        vote_group_id: proposal.vote_group_id,
+    };
+    Some(Sha256::digest(Encode!(&content).unwrap()).into())
+}
+
+#[ic_cdk_macros::query]
+fn get_proposal_count() -> u64 {
+    PROPOSAL_MAP.with(|p| p.borrow().len())
+}
+
+#[ic_cdk_macros::query]
+fn count_active_by_owner(who: candid::Principal) -> u64 {
+    let now = env::time();
+    PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.is_open(now) && proposal.owner == who)
+            .count() as u64
+    })
+}
+
+#[ic_cdk_macros::query]
+fn get_archived(key: u64) -> Option<Proposal> {
+    ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)).map(|proposal| proposal.public_view(key))
+}
+
+#[ic_cdk_macros::query]
+fn get_voters(key: u64, offset: u64, limit: u64) -> Vec<candid::Principal> {
+    let proposal = match PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)) {
+        Some(value) => lagged_view(key, value),
+        None => return vec![],
+    };
+    if proposal.private_voters {
+        return vec![];
+    }
+    proposal
+        .voted
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit.min(MAX_PAGE_SIZE) as usize)
+        .collect()
+}
+
+#[ic_cdk_macros::query]
+fn list_proposals(offset: u64, limit: u64, include_archived: bool) -> Vec<(u64, Proposal)> {
+    page_proposals(offset, limit, include_archived, false)
+        .into_iter()
+        .map(|(key, proposal)| (key, proposal.public_view(key)))
+        .collect()
+}
+
+fn page_proposals(
+    offset: u64,
+    limit: u64,
+    include_archived: bool,
+    include_unlisted: bool,
+) -> Vec<(u64, Proposal)> {
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let shown = |(_, proposal): &(u64, Proposal)| include_unlisted || proposal.listed;
+    let mut proposals: Vec<(u64, Proposal)> = PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(shown)
+            .skip(offset as usize)
+            .take(limit)
+            .collect()
+    });
+    if include_archived && proposals.len() < limit {
+        // Archived proposals are paged after the live ones.
+        let hot_len = PROPOSAL_MAP.with(|p| {
+            let map = p.borrow();
+            if include_unlisted {
+                map.len()
+            } else {
+                map.iter().filter(shown).count() as u64
+            }
+        });
+        let skip = offset.saturating_sub(hot_len) as usize;
+        ARCHIVE_MAP.with(|a| {
+            let archived = a.borrow();
+            proposals.extend(archived.iter().filter(shown).skip(skip).take(limit - proposals.len()))
+        });
+    }
+    proposals
+}
+
+// `list_proposals` over live proposals, each with the status `get_proposal_status`
+// would report for it.
+#[ic_cdk_macros::query]
+fn list_with_status(offset: u64, limit: u64) -> Vec<(u64, Proposal, ProposalStatus)> {
+    page_proposals(offset, limit, false, false)
+        .into_iter()
+        .map(|(key, proposal)| {
+            let status = reported_status(key, proposal.clone());
+            (key, proposal.public_view(key), status)
+        })
+        .collect()
+}
+
+// Active proposals the caller may still vote on.
+#[ic_cdk_macros::query]
+fn my_eligible_proposals(offset: u64, limit: u64) -> Vec<(u64, Proposal)> {
+    let caller = env::caller();
+    if config().require_registration
+        && !VOTER_MAP.with(|v| v.borrow().contains_key(&StorablePrincipal(caller)))
+    {
+        return vec![];
+    }
+    let now = env::time();
+    PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(key, proposal)| {
+                proposal.listed
+                    && proposal.is_open(now)
+                    && !proposal.voted.contains(&caller)
+                    && is_eligible(*key, caller)
+            })
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(key, proposal)| (key, proposal.public_view(key)))
+            .collect()
+    })
+}
+
+// Keys in `start..end`, i.e. `end` is exclusive.
+#[ic_cdk_macros::query]
+fn get_proposals_in_range(start: u64, end: u64, limit: u64) -> Vec<(u64, Proposal)> {
+    if start >= end {
+        return vec![];
+    }
+    PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .range(start..end)
+            .filter(|(_, proposal)| proposal.listed)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(key, proposal)| (key, proposal.public_view(key)))
+            .collect()
+    })
+}
+
+#[ic_cdk_macros::query]
+fn search(query: String, offset: u64, limit: u64) -> Result<Vec<(u64, Proposal)>, VoteError> {
+    check_scan_balance()?;
+    let query = query.to_lowercase();
+    Ok(PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| {
+                proposal.listed && proposal.description.to_lowercase().contains(&query)
+            })
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(key, proposal)| (key, proposal.public_view(key)))
+            .collect()
+    }))
+}
+
+#[ic_cdk_macros::query]
+fn list_proposals_filtered(
+    filter: ProposalFilter,
+    offset: u64,
+    limit: u64,
+) -> Result<Vec<(u64, Proposal)>, VoteError> {
+    check_scan_balance()?;
+    Ok(PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.listed && filter.matches(proposal))
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(key, proposal)| (key, proposal.public_view(key)))
+            .collect()
+    }))
+}
+
+// Size of the full `list_proposals_filtered` result, without fetching it.
+#[ic_cdk_macros::query]
+fn count_matching(filter: ProposalFilter) -> Result<u64, VoteError> {
+    check_scan_balance()?;
+    Ok(PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.listed && filter.matches(proposal))
+            .count() as u64
+    }))
+}
+
+// Unredacted dump of live and archived proposals, e.g. for migrations.
+#[ic_cdk_macros::query]
+fn export_all(offset: u64, limit: u64) -> Result<Vec<(u64, Proposal)>, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    check_scan_balance()?;
+    Ok(page_proposals(offset, limit, true, true))
+}
+
+fn check_not_frozen() -> Result<(), VoteError> {
+    if config().frozen {
+        return Err(VoteError::CanisterFrozen);
+    }
+    Ok(())
+}
+
+fn check_not_anonymous(who: candid::Principal) -> Result<(), VoteError> {
+    if who == candid::Principal::anonymous() && !config().allow_anonymous {
+        return Err(VoteError::Unauthorized);
+    }
+    Ok(())
+}
+
+fn check_scan_balance() -> Result<(), VoteError> {
+    match config().min_scan_balance {
+        Some(floor) if env::canister_balance() < floor => Err(VoteError::InsufficientCycles),
+        _ => Ok(()),
+    }
+}
+
+#[ic_cdk_macros::query]
+fn get_proposal_status(key: u64) -> Option<&'static str> {
+    visible_status(key).map(ProposalStatus::as_str)
+}
+
+// Positional: the n-th entry is the status of the n-th key.
+#[ic_cdk_macros::query]
+fn batch_status(keys: Vec<u64>) -> Vec<Option<ProposalStatus>> {
+    if keys.len() > MAX_BATCH_SIZE {
+        env::trap("too many keys in a single batch");
+    }
+    keys.into_iter().map(visible_status).collect()
+}
+
+// The status as reported to clients, i.e. Undecided while counts are hidden.
+fn visible_status(key: u64) -> Option<ProposalStatus> {
+    if config().tally_delay_ns > 0 {
+        let proposal = PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key))?;
+        return Some(reported_status(key, proposal));
+    }
+    match proposal_status(key)? {
+        ProposalStatus::Undecided => Some(ProposalStatus::Undecided),
+        status => match PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)) {
+            Some(proposal) if counts_hidden(&proposal) => Some(ProposalStatus::Undecided),
+            _ => Some(status),
+        },
+    }
+}
+
+// Uncached variant of `visible_status` for callers that already hold the proposal.
+fn reported_status(key: u64, proposal: Proposal) -> ProposalStatus {
+    let proposal = lagged_view(key, proposal);
+    if counts_hidden(&proposal) {
+        return ProposalStatus::Undecided;
+    }
+    compute_status(key, &proposal)
+}
+
+// Live listed proposals only, active and closed alike, each evaluated as clients see it.
+#[ic_cdk_macros::query]
+fn count_by_status() -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    PROPOSAL_MAP.with(|p| {
+        for (key, proposal) in p.borrow().iter().filter(|(_, proposal)| proposal.listed) {
+            match reported_status(key, proposal) {
+                ProposalStatus::Undecided => counts.undecided += 1,
+                ProposalStatus::Approved => counts.approved += 1,
+                ProposalStatus::Rejected => counts.rejected += 1,
+                ProposalStatus::Passed => counts.passed += 1,
+            }
+        }
+    });
+    counts
+}
+
+// Counts stay hidden until enough principals voted that no single early
+// voter can be singled out from them.
+fn counts_hidden(proposal: &Proposal) -> bool {
+    proposal.voter_count() < config().min_reveal_voters as u64
+}
+
+// E.g. "Proposal 42 'Raise cap' APPROVED with 62% (31/50)", where the share is
+// of the counted votes. Hidden counts only ever give the undecided form.
+#[ic_cdk_macros::query]
+fn get_result_announcement(key: u64) -> Option<String> {
+    let proposal = lagged_view(key, PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key))?);
+    let title = format!("Proposal {} '{}'", key, proposal.description);
+    if counts_hidden(&proposal) {
+        return Some(format!("{} is UNDECIDED", title));
+    }
+    let tally = outcome_tally(key, &proposal);
+    let (approval_pct, rejection_pct, pass_pct) = percentages(&tally, proposal.binary);
+    let total = tally.counted(proposal.binary);
+    let status = compute_status(key, &proposal);
+    let (pct, votes) = match status {
+        ProposalStatus::Approved => (approval_pct, tally.approve),
+        ProposalStatus::Rejected => (rejection_pct, tally.reject),
+        ProposalStatus::Passed => (pass_pct, tally.pass),
+        ProposalStatus::Undecided => {
+            return Some(format!("{} is UNDECIDED after {} votes", title, total));
+        }
+    };
+    Some(format!(
+        "{} {} with {:.0}% ({}/{})",
+        title,
+        status.as_str().to_uppercase(),
+        pct,
+        votes,
+        total
+    ))
+}
+
+#[ic_cdk_macros::query]
+fn get_proposal_metrics(key: u64) -> Option<Metrics> {
+    let proposal = PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .map(|proposal| lagged_view(key, proposal))
+        .filter(|proposal| !counts_hidden(proposal))?;
+    let (approval_pct, rejection_pct, pass_pct) =
+        percentages(&outcome_tally(key, &proposal), proposal.binary);
+    Some(Metrics {
+        approval_pct,
+        rejection_pct,
+        pass_pct,
+        turnout: proposal.voter_count() as u32,
+        quorum_met: quorum_met(key, &proposal),
+    })
+}
+
+// Over the owner's listed proposals, live and archived, with outcomes as
+// clients see them.
+#[ic_cdk_macros::query]
+fn owner_stats(who: candid::Principal) -> OwnerStats {
+    let mut stats = OwnerStats::default();
+    let mut voters = 0u64;
+    let mut count = |key: u64, proposal: Proposal| {
+        if proposal.owner != who || !proposal.listed {
+            return;
+        }
+        stats.total_created += 1;
+        voters += proposal.voter_count();
+        match reported_status(key, proposal) {
+            ProposalStatus::Approved => stats.total_approved += 1,
+            ProposalStatus::Rejected => stats.total_rejected += 1,
+            _ => (),
+        }
+    };
+    PROPOSAL_MAP.with(|p| p.borrow().iter().for_each(|(key, proposal)| count(key, proposal)));
+    ARCHIVE_MAP.with(|a| a.borrow().iter().for_each(|(key, proposal)| count(key, proposal)));
+    if stats.total_created > 0 {
+        stats.avg_turnout = voters as f64 / stats.total_created as f64;
+    }
+    stats
+}
+
+// Average number of voters per live listed proposal; eligibility lists are not taken
+// into account because most proposals are open to everyone.
+#[ic_cdk_macros::query]
+fn overall_participation() -> f64 {
+    let (proposals, voters) = PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.listed)
+            .fold((0u64, 0u64), |(proposals, voters), (_, proposal)| {
+                (proposals + 1, voters + proposal.voter_count())
+            })
+    });
+    if proposals == 0 {
+        return 0.0;
+    }
+    voters as f64 / proposals as f64
+}
+
+#[ic_cdk_macros::query]
+fn get_tally(key: u64) -> Option<Tally> {
+    PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .and_then(|proposal| reported_tally(key, proposal))
+}
+
+// The counters as clients may see them, `None` while they are hidden.
+fn reported_tally(key: u64, proposal: Proposal) -> Option<Tally> {
+    Some(lagged_view(key, proposal))
+        .filter(|proposal| !counts_hidden(proposal))
+        .map(|proposal| proposal.tally())
+}
+
+// Stores the current counters and drops history that no delayed read can reach.
+fn record_tally(key: u64, proposal: &Proposal) {
+    let now = env::time();
+    let cutoff = now.saturating_sub(history_window());
+    let snapshot = TallySnapshot::of(proposal);
+    TALLY_HISTORY.with(|h| {
+        let mut history = h.borrow_mut();
+        history.insert((key, now), snapshot);
+        // Everything before the newest entry at or before the cutoff is unreachable.
+        let stale: Vec<_> = history.range((key, 0)..=(key, cutoff)).map(|(k, _)| k).collect();
+        for entry in stale.iter().rev().skip(1) {
+            history.remove(entry);
+        }
+    });
+}
+
+// How far back TALLY_HISTORY has to reach for delayed and historical reads.
+fn history_window() -> u64 {
+    let config = config();
+    config.tally_delay_ns.max(config.tally_history_retention_ns)
+}
+
+// `None` if the proposal did not exist yet at `time_ns`, if that time is older
+// than the retained history, or if counts were still hidden back then.
+#[ic_cdk_macros::query]
+fn get_tally_as_of(key: u64, time_ns: u64) -> Option<Tally> {
+    if time_ns < env::time().saturating_sub(history_window()) {
+        return None;
+    }
+    let (_, snapshot) =
+        TALLY_HISTORY.with(|h| h.borrow().range((key, 0)..=(key, time_ns)).last())?;
+    if snapshot.voter_count() < config().min_reveal_voters as u64 {
+        return None;
+    }
+    Some(snapshot.tally)
+}
+
+// The proposal with its counters rolled back to `tally_delay_ns` ago. Proposals
+// without any recorded history are returned unchanged.
+fn lagged_view(key: u64, mut proposal: Proposal) -> Proposal {
+    let delay = config().tally_delay_ns;
+    if delay == 0 {
+        return proposal;
+    }
+    let cutoff = tally_cutoff();
+    let snapshot = TALLY_HISTORY.with(|h| {
+        let history = h.borrow();
+        match history.range((key, 0)..=(key, cutoff)).last() {
+            Some(((_, at), snapshot)) => Some((at, snapshot)),
+            None => history
+                .range((key, 0)..=(key, u64::MAX))
+                .next()
+                .map(|_| (0, TallySnapshot::default())),
+        }
+    });
+    if let Some((at, snapshot)) = snapshot {
+        proposal.set_tally(snapshot.tally);
+        proposal.voted.truncate(snapshot.voters as usize);
+        proposal.anonymous_ballots = proposal
+            .anonymous_ballots
+            .min(snapshot.anonymous_ballots.unwrap_or(0));
+        // A counter that changed since has held its lagged value at least from
+        // the snapshot on; later stamps would date the hidden votes.
+        for reached in [
+            &mut proposal.reached_at.approve,
+            &mut proposal.reached_at.reject,
+            &mut proposal.reached_at.pass,
+        ] {
+            if *reached > cutoff {
+                *reached = at;
+            }
+        }
+    }
+    proposal
+}
+
+// Ballots cast after this time are not visible to clients yet.
+fn tally_cutoff() -> u64 {
+    env::time().saturating_sub(config().tally_delay_ns)
+}
+
+// Direct votes plus one vote per live delegation whose delegate voted and
+// whose delegator did not. Delegations are not followed transitively.
+#[ic_cdk_macros::query]
+fn get_delegated_tally(key: u64) -> Option<Tally> {
+    let proposal = lagged_view(key, PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key))?);
+    if counts_hidden(&proposal) {
+        return None;
+    }
+    let mut tally = proposal.tally();
+    let now = env::time();
+    let cutoff = tally_cutoff();
+    DELEGATION_MAP.with(|d| {
+        VOTE_MAP.with(|v| {
+            let v = v.borrow();
+            // Only ballots the lagged counters already include.
+            let visible = |who| v.get(&(key, who)).filter(|record| record.timestamp <= cutoff);
+            for (delegator, delegation) in d.borrow().iter() {
+                if !delegation.is_live(now) || visible(delegator).is_some() {
+                    continue;
+                }
+                if let Some(record) = visible(StorablePrincipal(delegation.to)) {
+                    tally.add(record.choice, vote_weight(key, &proposal, delegator.0));
+                }
+            }
+        })
+    });
+    Some(tally)
+}
+
+// (votes counted towards quorum, votes quorum needs); the first may exceed the second.
+#[ic_cdk_macros::query]
+fn quorum_progress(key: u64) -> Option<(u64, u64)> {
+    let proposal = lagged_view(key, PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key))?);
+    Some((turnout(key, &proposal), quorum_needed(key, &proposal)))
+}
+
+// Live proposals with a definitive outcome, open or closed, as clients see them.
+#[ic_cdk_macros::query]
+fn get_decided_proposals(offset: u64, limit: u64) -> Vec<(u64, ProposalStatus)> {
+    PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.listed)
+            .map(|(key, proposal)| (key, reported_status(key, proposal)))
+            .filter(|(_, status)| *status != ProposalStatus::Undecided)
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    })
+}
+
+// Open proposals, or closed ones left without an outcome, whose leading options
+// are tied on the votes that count towards the outcome.
+#[ic_cdk_macros::query]
+fn get_tied_proposals(offset: u64, limit: u64) -> Vec<u64> {
+    let now = env::time();
+    PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.listed)
+            .map(|(key, proposal)| (key, lagged_view(key, proposal)))
+            .filter(|(key, proposal)| {
+                !counts_hidden(proposal)
+                    && earliest_of_tied(&outcome_tally(*key, proposal), proposal).is_some()
+                    && (proposal.is_open(now)
+                        || compute_status(*key, proposal) == ProposalStatus::Undecided)
+            })
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(key, _)| key)
+            .collect()
+    })
+}
+
+// Active undecided proposals with the number of votes still missing for quorum
+// (zero when quorum is met but no option has a majority).
+#[ic_cdk_macros::query]
+fn get_undecided_proposals(offset: u64, limit: u64) -> Vec<(u64, u64)> {
+    let now = env::time();
+    PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(key, proposal)| {
+                proposal.listed
+                    && proposal.is_open(now)
+                    && reported_status(*key, proposal.clone()) == ProposalStatus::Undecided
+            })
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(key, proposal)| {
+                let proposal = lagged_view(key, proposal);
+                let missing = quorum_needed(key, &proposal).saturating_sub(turnout(key, &proposal));
+                (key, missing)
+            })
+            .collect()
+    })
+}
+
+#[ic_cdk_macros::query]
+fn is_decided(key: u64) -> bool {
+    // Undecided also covers proposals that have not reached the vote quorum.
+    matches!(visible_status(key), Some(status) if status != ProposalStatus::Undecided)
+}
+
+fn proposal_status(key: u64) -> Option<ProposalStatus> {
+    if let Some(status) = STATUS_CACHE.with(|c| c.borrow().get(&key).copied()) {
+        return Some(status);
+    }
+    PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)).map(|proposal| compute_status(key, &proposal))
+}
+
+fn compute_status(key: u64, proposal: &Proposal) -> ProposalStatus {
+    if !quorum_met(key, proposal) {
+        // The proposal does not have enough votes for evaluation.
+        return ProposalStatus::Undecided;
+    }
+
+    let config = config();
+    let tally = outcome_tally(key, proposal);
+    let winner = match earliest_of_tied(&tally, proposal) {
+        Some(status) if config.tie_break == TieBreak::EarliestMajority => status,
+        _ => {
+            let (approval_percentage, rejection_percentage, pass_percentage) =
+                percentages(&tally, proposal.binary);
+            // Under a strict majority a share exactly at the threshold is not enough,
+            // so e.g. a 50/50 split stays undecided.
+            let strict = config.strict_majority;
+            let threshold = proposal.threshold_pct.unwrap_or(50) as f64;
+            let wins = |pct: f64| if strict { pct > threshold } else { pct >= threshold };
+            if wins(approval_percentage) {
+                ProposalStatus::Approved
+            } else if wins(rejection_percentage) {
+                ProposalStatus::Rejected
+            } else if !proposal.binary && wins(pass_percentage) {
+                ProposalStatus::Passed
+            } else {
+                ProposalStatus::Undecided
+            }
+        }
+    };
+    let winning_votes = match winner {
+        ProposalStatus::Approved => tally.approve,
+        ProposalStatus::Rejected => tally.reject,
+        ProposalStatus::Passed => tally.pass,
+        ProposalStatus::Undecided => return ProposalStatus::Undecided,
+    };
+    // A high share from a handful of votes is not enough on its own.
+    let approve_count_met =
+        config.min_approve_count.is_none_or(|min| tally.approve >= u64::from(min));
+    let floor_met = proposal.min_winning_votes.is_none_or(|min| winning_votes >= min);
+    if floor_met && (winner != ProposalStatus::Approved || approve_count_met) {
+        winner
+    } else {
+        ProposalStatus::Undecided
+    }
+}
+
+// Winner among the options sharing the highest count, or `None` without a tie.
+fn earliest_of_tied(tally: &Tally, proposal: &Proposal) -> Option<ProposalStatus> {
+    let times = proposal.reached_at;
+    let mut options = vec![
+        (tally.approve, times.approve, ProposalStatus::Approved),
+        (tally.reject, times.reject, ProposalStatus::Rejected),
+    ];
+    if !proposal.binary {
+        options.push((tally.pass, times.pass, ProposalStatus::Passed));
+    }
+    let top = options.iter().map(|(count, _, _)| *count).max()?;
+    let tied: Vec<_> = options.into_iter().filter(|(count, _, _)| *count == top).collect();
+    if top == 0 || tied.len() < 2 {
+        return None;
+    }
+    tied.into_iter()
+        .min_by_key(|(_, reached_at, _)| *reached_at)
+        .map(|(_, _, status)| status)
+}
+
+// (approve, reject, pass) percentages; all zero when nothing was counted.
+fn percentages(tally: &Tally, binary: bool) -> (f64, f64, f64) {
+    let total_votes = tally.counted(binary);
+    if total_votes == 0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let pass = if binary { 0 } else { tally.pass };
+    (
+        (tally.approve as f64 / total_votes as f64) * 100.0,
+        (tally.reject as f64 / total_votes as f64) * 100.0,
+        (pass as f64 / total_votes as f64) * 100.0,
+    )
+}
+
+// The counters that decide the outcome, which may differ from the raw ones.
+fn outcome_tally(key: u64, proposal: &Proposal) -> Tally {
+    let mut tally = proposal.tally();
+    if !proposal.owner_vote_counts {
+        let owner_vote = VOTE_MAP.with(|v| v.borrow().get(&(key, StorablePrincipal(proposal.owner))));
+        if let Some(record) = owner_vote {
+            tally.subtract(&record.cast);
+        }
+    }
+    tally
+}
+
+// A percentage quorum is measured against the current eligible set, so it
+// moves whenever voters are added to or removed from that set.
+fn quorum_met(key: u64, proposal: &Proposal) -> bool {
+    turnout(key, proposal) >= quorum_needed(key, proposal)
+}
+
+// Voters counted towards quorum.
+fn turnout(key: u64, proposal: &Proposal) -> u64 {
+    if config().pass_is_turnout || proposal.private_tally {
+        return proposal.voter_count();
+    }
+    VOTE_MAP.with(|v| {
+        let votes = v.borrow();
+        proposal
+            .voted
+            .iter()
+            .filter(|voter| {
+                votes
+                    .get(&(key, StorablePrincipal(**voter)))
+                    .is_some_and(|record| record.cast.counted(true) > 0)
+            })
+            .count() as u64
+    })
+}
+
+fn quorum_needed(key: u64, proposal: &Proposal) -> u64 {
+    let absolute = proposal.quorum.unwrap_or(QUORUM);
+    match proposal.quorum_pct {
+        Some(pct) => match eligible_count(key) {
+            0 => absolute,
+            eligible => (eligible * pct as u64).div_ceil(100),
+        },
+        None => absolute,
+    }
+}
+
+// Queries cannot persist state, so the cache is refreshed by every update
+// that changes a proposal rather than lazily on read.
+fn refresh_status(key: u64, proposal: &Proposal) -> ProposalStatus {
+    let status = compute_status(key, proposal);
+    STATUS_CACHE.with(|c| c.borrow_mut().insert(key, status));
+    status
+}
+
+fn evict_status(key: u64) {
+    STATUS_CACHE.with(|c| c.borrow_mut().remove(&key));
+}
+
+
+
+fn description_hash(description: &str) -> [u8; 32] {
+    let normalized = description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    Sha256::digest(normalized.as_bytes()).into()
+}
+
+// Unlike the global check this covers closed proposals too, which rules out the
+// active-only index and needs a scan of the live map.
+fn owner_has_duplicate(key: u64, owner: candid::Principal, description: &str) -> bool {
+    let hash = description_hash(description);
+    PROPOSAL_MAP.with(|p| {
+        p.borrow().iter().any(|(k, proposal)| {
+            k != key && proposal.owner == owner && description_hash(&proposal.description) == hash
+        })
+    })
+}
+
+// Proposals past their deadline are only unindexed once they are closed, so
+// the index may still list them.
+fn has_active_duplicate(key: u64, description: &str) -> bool {
+    let hash = description_hash(description);
+    let now = env::time();
+    DESCRIPTION_INDEX.with(|d| {
+        d.borrow()
+            .range((hash, 0)..)
+            .take_while(|((h, _), _)| *h == hash)
+            .any(|((_, k), _)| {
+                k != key
+                    && PROPOSAL_MAP
+                        .with(|p| read_entry(&p.borrow(), k))
+                        .is_some_and(|proposal| !proposal.is_expired(now))
+            })
+    })
+}
+
+fn index_description(key: u64, proposal: &Proposal) {
+    if proposal.is_active {
+        let hash = description_hash(&proposal.description);
+        DESCRIPTION_INDEX.with(|d| d.borrow_mut().insert((hash, key), ()));
+    }
+}
+
+fn unindex_description(key: u64, proposal: &Proposal) {
+    let hash = description_hash(&proposal.description);
+    DESCRIPTION_INDEX.with(|d| d.borrow_mut().remove(&(hash, key)));
+}
+
+// Only active proposals are indexed, like descriptions, and closing one drops it.
+fn index_deadline(key: u64, proposal: &Proposal) {
+    if let (true, Some(deadline)) = (proposal.is_active, proposal.deadline) {
+        DEADLINES.with(|d| d.borrow_mut().insert((deadline, key)));
+    }
+}
+
+fn unindex_deadline(key: u64, proposal: &Proposal) {
+    if let Some(deadline) = proposal.deadline {
+        DEADLINES.with(|d| d.borrow_mut().remove(&(deadline, key)));
+    }
+}
+
+fn validate_quorum(quorum_pct: Option<u8>) -> Result<(), VoteError> {
+    match quorum_pct {
+        Some(pct) if pct == 0 || pct > 100 => Err(VoteError::InvalidQuorum),
+        _ => Ok(()),
+    }
+}
+
+fn validate_tags(tags: &Option<Vec<String>>) -> Result<(), VoteError> {
+    let tags = match tags {
+        Some(tags) => tags,
+        None => return Ok(()),
+    };
+    if tags.len() > MAX_TAGS
+        || tags.iter().any(|tag| tag.trim().is_empty() || tag.len() > MAX_TAG_LEN)
+    {
+        return Err(VoteError::InvalidTag);
+    }
+    Ok(())
+}
+
+// Tags compare case-insensitively, so the registry counts each spelling once.
+fn tag_keys(tags: &[String]) -> HashSet<String> {
+    tags.iter().map(|tag| tag.to_ascii_lowercase()).collect()
+}
+
+fn check_tag_limit(tags: &[String]) -> Result<(), VoteError> {
+    let max = match config().max_distinct_tags {
+        Some(max) => max,
+        None => return Ok(()),
+    };
+    TAG_REGISTRY.with(|r| {
+        let registry = r.borrow();
+        let new_tags = tag_keys(tags)
+            .into_iter()
+            .filter(|tag| !registry.contains_key(&StorableString(tag.clone())))
+            .count() as u64;
+        if new_tags > 0 && registry.len() + new_tags > max {
+            return Err(VoteError::TagLimitReached);
+        }
+        Ok(())
+    })
+}
+
+// Most used first, ties in alphabetical order. Tags are reported lowercased.
+#[ic_cdk_macros::query]
+fn list_tags(offset: u64, limit: u64) -> Vec<(String, u64)> {
+    let mut tags: Vec<(String, u64)> =
+        TAG_REGISTRY.with(|r| r.borrow().iter().map(|(tag, count)| (tag.0, count)).collect());
+    tags.sort_by(|(a_tag, a_count), (b_tag, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_tag.cmp(b_tag))
+    });
+    tags.into_iter()
+        .skip(offset as usize)
+        .take(limit.min(MAX_PAGE_SIZE) as usize)
+        .collect()
+}
+
+fn register_tags(tags: &[String]) {
+    TAG_REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        for tag in tag_keys(tags) {
+            let tag = StorableString(tag);
+            let count = registry.get(&tag).unwrap_or(0);
+            registry.insert(tag, count + 1);
+        }
+    });
+}
+
+fn unregister_tags(tags: &[String]) {
+    TAG_REGISTRY.with(|r| {
+        let mut registry = r.borrow_mut();
+        for tag in tag_keys(tags) {
+            let tag = StorableString(tag);
+            match registry.get(&tag) {
+                Some(count) if count > 1 => {
+                    registry.insert(tag, count - 1);
+                }
+                _ => {
+                    registry.remove(&tag);
+                }
+            }
+        }
+    });
+}
+
+fn validate_execution(execution: &Option<ExecutionPayload>) -> Result<(), VoteError> {
+    match execution {
+        Some(payload)
+            if payload.method.is_empty()
+                || payload.method.len() > MAX_EXECUTION_METHOD_LEN
+                || payload.args.len() > MAX_EXECUTION_ARGS_LEN =>
+        {
+            Err(VoteError::InvalidExecution)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_attachments(attachments: &Option<Vec<Attachment>>) -> Result<(), VoteError> {
+    let attachments = match attachments {
+        Some(attachments) => attachments,
+        None => return Ok(()),
+    };
+    if attachments.len() > MAX_ATTACHMENTS
+        || attachments.iter().any(|a| {
+            a.name.is_empty()
+                || a.name.len() > MAX_ATTACHMENT_NAME_LEN
+                || a.url.len() > MAX_ATTACHMENT_URL_LEN
+        })
+    {
+        return Err(VoteError::InvalidAttachment);
+    }
+    Ok(())
+}
+
+fn new_proposal(proposal: CreateProposal, owner: candid::Principal) -> Proposal {
+    Proposal {
+        description: proposal.description,
+        approve: 0,
+        reject: 0,
+        pass: 0,
+        is_active: proposal.is_active,
+        voted: vec![],
+        owner,
+        private_voters: proposal.private_voters.unwrap_or(false),
+        binary: proposal.binary.unwrap_or(false),
+        quorum_pct: proposal.quorum_pct.or(config().default_quorum_pct),
+        created_at: env::time(),
+        updated_at: env::time(),
+        owner_vote_counts: proposal.owner_vote_counts.unwrap_or(true),
+        weighted: proposal.weighted.unwrap_or(false),
+        tags: proposal.tags.unwrap_or_default(),
+        secret_hash: proposal.secret.as_deref().and_then(secret_hash),
+        attachments: proposal.attachments.unwrap_or_default(),
+        human_id: None,
+        reached_at: OptionTimes::default(),
+        quorum: None,
+        threshold_pct: None,
+        min_winning_votes: None,
+        listed: proposal.listed.unwrap_or(true),
+        lock_on_first_vote: proposal.lock_on_first_vote.unwrap_or(false),
+        execution: proposal.execution,
+        executed: false,
+        private_tally: proposal.private_tally.unwrap_or(false),
+        anonymous_ballots: 0,
+        deadline: proposal.deadline.or_else(|| {
+            config().default_ttl_ns.map(|ttl| env::time().saturating_add(ttl))
+        }),
+        vote_group_id: proposal.vote_group_id,
+    }
+}
+
+fn secret_hash(secret: &str) -> Option<[u8; 32]> {
+    match secret {
+        "" => None,
+        secret => Some(Sha256::digest(secret.as_bytes()).into()),
+    }
+}
+
+// Whether the proposal would still fit into a stable map slot once
+// `projected_voters` principals of maximal length have voted on it.
+#[ic_cdk_macros::query]
+fn would_fit(proposal: CreateProposal, projected_voters: u32) -> bool {
+    if projected_voters > MAX_VALUE_SIZE {
+        // Every voter takes at least one byte, so this can never fit.
+        return false;
+    }
+    let widest = candid::Principal::from_slice(&[0xff; 29]);
+    let mut value = new_proposal(proposal, widest);
+    value.voted = vec![widest; projected_voters as usize];
+    match Encode!(&value) {
+        Ok(bytes) => bytes.len() <= MAX_VALUE_SIZE as usize,
+        Err(_) => false,
+    }
+}
+
+#[ic_cdk_macros::update]
+fn create_proposal(key: u64, proposal: CreateProposal) -> Result<Option<Proposal>, VoteError> {
+    if config().pow_difficulty.is_some() {
+        return Err(VoteError::InvalidProofOfWork);
+    }
+    store_proposal(key, proposal, None)
+}
+
+// `nonce` must make SHA-256(caller || key || nonce) start with `pow_difficulty`
+// zero bits. Binding the key stops one solved nonce from being reused.
+#[ic_cdk_macros::update]
+fn create_proposal_pow(
+    key: u64,
+    proposal: CreateProposal,
+    nonce: u64,
+) -> Result<Option<Proposal>, VoteError> {
+    check_not_frozen()?;
+    let difficulty = config().pow_difficulty.unwrap_or(0) as u32;
+    let mut hasher = Sha256::new();
+    hasher.update(env::caller().as_slice());
+    hasher.update(key.to_be_bytes());
+    hasher.update(nonce.to_be_bytes());
+    if leading_zero_bits(&hasher.finalize()) < difficulty {
+        return Err(VoteError::InvalidProofOfWork);
+    }
+    store_proposal(key, proposal, None)
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn store_proposal(
+    key: u64,
+    proposal: CreateProposal,
+    human_id: Option<String>,
+) -> Result<Option<Proposal>, VoteError> {
+    check_not_frozen()?;
+    check_not_anonymous(env::caller())?;
+    mark_seen(env::caller());
+    // An archived key stays taken: re-creating it would shadow the archived record.
+    if RESERVATIONS.with(|r| r.borrow().contains_key(&key))
+        || ARCHIVE_MAP.with(|a| a.borrow().contains_key(&key))
+    {
+        return Err(VoteError::ProposalAlreadyExists);
+    }
+    // Only its owner may start a live proposal over.
+    let previous = PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key));
+    if previous.as_ref().is_some_and(|p| p.owner != env::caller()) {
+        return Err(VoteError::ProposalAlreadyExists);
+    }
+    validate_description(&proposal.description)?;
+    validate_quorum(proposal.quorum_pct)?;
+    validate_tags(&proposal.tags)?;
+    validate_attachments(&proposal.attachments)?;
+    validate_execution(&proposal.execution)?;
+    check_tag_limit(proposal.tags.as_deref().unwrap_or_default())?;
+    if config().reject_duplicate_descriptions && has_active_duplicate(key, &proposal.description) {
+        return Err(VoteError::DuplicateProposal);
+    }
+    if config().owner_unique_descriptions
+        && owner_has_duplicate(key, env::caller(), &proposal.description)
+    {
+        return Err(VoteError::DuplicateProposal);
+    }
+    let owner = StorablePrincipal(env::caller());
+    let now = env::time();
+    let last_create = LAST_CREATE.with(|l| l.borrow().get(&owner));
+    if last_create.is_some_and(|last| now.saturating_sub(last) < config().min_create_interval_ns) {
+        return Err(VoteError::RateLimited);
+    }
+    let was_active = previous.as_ref().is_some_and(|p| p.is_active);
+    if proposal.is_active && !was_active {
+        check_active_limit()?;
+    }
+    let mut value = new_proposal(proposal, env::caller());
+    value.human_id = human_id;
+    check_encoded_size(&value)?;
+    LAST_CREATE.with(|l| l.borrow_mut().insert(owner, now));
+    // Overwriting starts a new proposal: ballots, comments and history of the
+    // old one must not carry over.
+    if let Some(previous) = &previous {
+        unindex_description(key, previous);
+        unindex_deadline(key, previous);
+        unindex_group(key, previous);
+        release_human_id(previous);
+        unregister_tags(&previous.tags);
+        remove_side_entries(key);
+    }
+    index_description(key, &value);
+    index_deadline(key, &value);
+    index_group(key, &value);
+    register_tags(&value.tags);
+    refresh_status(key, &value);
+    record_tally(key, &value);
+    record_revision(key, &value);
+    log_event(key, EventKind::Created { is_active: value.is_active });
+    adjust_active_count(was_active, value.is_active);
+    if value.weighted {
+        snapshot_weights(key);
+    }
+    let previous = PROPOSAL_MAP.with(|p| p.borrow_mut().insert(key, value));
+    certify_tally(key);
+    Ok(previous.map(|proposal| proposal.public_view(key)))
+}
+
+
+// One past the highest key in use, whether live, archived or reserved.
+fn next_free_key() -> u64 {
+    let last_key = |map: &StableBTreeMap<u64, Proposal, Memory>| {
+        map.last_key_value().map(|(key, _)| key)
+    };
+    PROPOSAL_MAP
+        .with(|p| last_key(&p.borrow()))
+        .max(ARCHIVE_MAP.with(|a| last_key(&a.borrow())))
+        .max(RESERVATIONS.with(|r| r.borrow().last_key_value().map(|(key, _)| key)))
+        .map_or(0, |key| key + 1)
+}
+
+#[ic_cdk_macros::update]
+fn reserve_key() -> Result<u64, VoteError> {
+    check_not_frozen()?;
+    let caller = env::caller();
+    check_not_anonymous(caller)?;
+    mark_seen(caller);
+    let key = next_free_key();
+    let reservation = Reservation {
+        owner: caller,
+        reserved_at: env::time(),
+    };
+    RESERVATIONS.with(|r| r.borrow_mut().insert(key, reservation));
+    Ok(key)
+}
+
+// Creates the proposal under a key the caller reserved. The reservation is
+// used up only if creation succeeds, so a rejected proposal can be retried.
+#[ic_cdk_macros::update]
+fn finalize_reservation(key: u64, proposal: CreateProposal) -> Result<(), VoteError> {
+    let reservation = match RESERVATIONS.with(|r| r.borrow().get(&key)) {
+        Some(reservation) if !reservation.is_expired(env::time()) => reservation,
+        _ => return Err(VoteError::NoReservation),
+    };
+    if reservation.owner != env::caller() {
+        return Err(VoteError::Unauthorized);
+    }
+    if config().pow_difficulty.is_some() {
+        return Err(VoteError::InvalidProofOfWork);
+    }
+    RESERVATIONS.with(|r| r.borrow_mut().remove(&key));
+    if let Err(err) = store_proposal(key, proposal, None) {
+        RESERVATIONS.with(|r| r.borrow_mut().insert(key, reservation));
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn sweep_reservations() -> Result<u64, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    let now = env::time();
+    let expired: Vec<u64> = RESERVATIONS.with(|r| {
+        r.borrow()
+            .iter()
+            .filter(|(_, reservation)| reservation.is_expired(now))
+            .map(|(key, _)| key)
+            .collect()
+    });
+    RESERVATIONS.with(|r| {
+        let mut reservations = r.borrow_mut();
+        for key in &expired {
+            reservations.remove(key);
+        }
+    });
+    Ok(expired.len() as u64)
+}
+
+// Creates the proposal under the next free key and names it `<prefix>-<n>`,
+// with `n` counted per prefix.
+#[ic_cdk_macros::update]
+fn create_proposal_auto(
+    prefix: String,
+    proposal: CreateProposal,
+) -> Result<(u64, String), VoteError> {
+    if prefix.is_empty()
+        || prefix.len() > MAX_HUMAN_ID_PREFIX_LEN as usize
+        || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(VoteError::InvalidHumanId);
+    }
+    if config().pow_difficulty.is_some() {
+        return Err(VoteError::InvalidProofOfWork);
+    }
+    let key = next_free_key();
+    let prefix = StorableString(prefix);
+    let mut number = HUMAN_ID_COUNTERS.with(|c| c.borrow().get(&prefix)).unwrap_or(0) + 1;
+    // Imported proposals may already hold ids the counter has not reached yet.
+    let human_id = loop {
+        let human_id = format!("{}-{:03}", prefix.0, number);
+        if !HUMAN_ID_MAP.with(|h| h.borrow().contains_key(&StorableString(human_id.clone()))) {
+            break human_id;
+        }
+        number += 1;
+    };
+    // The id is part of the stored proposal, so it must count towards the size limit.
+    store_proposal(key, proposal, Some(human_id.clone()))?;
+    HUMAN_ID_COUNTERS.with(|c| c.borrow_mut().insert(prefix, number));
+    HUMAN_ID_MAP.with(|h| h.borrow_mut().insert(StorableString(human_id.clone()), key));
+    Ok((key, human_id))
+}
+
+// Migration helper that stores complete proposals, counters and voter lists
+// included. Individual vote records are not part of a `Proposal`, so `recount`
+// must not be used on imported proposals, and listed voters count as having
+// cast one ballot.
+#[ic_cdk_macros::update]
+fn import_proposals(items: Vec<(u64, Proposal)>) -> Vec<Result<(), VoteError>> {
+    if items.len() > MAX_BATCH_SIZE {
+        env::trap("too many proposals in a single batch");
+    }
+    if !is_admin(&env::caller()) {
+        return items.iter().map(|_| Err(VoteError::Unauthorized)).collect();
+    }
+    items
+        .into_iter()
+        .map(|(key, proposal)| import_proposal(key, proposal))
+        .collect()
+}
+
+// Measured up front so an oversized proposal is rejected with its size instead
+// of trapping when the stable map encodes it.
+fn check_encoded_size(proposal: &Proposal) -> Result<(), VoteError> {
+    let size = Encode!(proposal).map_or(usize::MAX, |bytes| bytes.len());
+    if size > MAX_VALUE_SIZE as usize {
+        return Err(VoteError::ProposalTooLarge {
+            size: size.min(u32::MAX as usize) as u32,
+            max: MAX_VALUE_SIZE,
+        });
+    }
+    Ok(())
+}
+
+fn import_proposal(key: u64, proposal: Proposal) -> Result<(), VoteError> {
+    let exists = PROPOSAL_MAP.with(|p| p.borrow().contains_key(&key))
+        || ARCHIVE_MAP.with(|a| a.borrow().contains_key(&key))
+        || RESERVATIONS.with(|r| r.borrow().contains_key(&key));
+    if exists {
+        return Err(VoteError::ProposalAlreadyExists);
+    }
+    check_encoded_size(&proposal)?;
+    validate_tags(&Some(proposal.tags.clone()))?;
+    check_tag_limit(&proposal.tags)?;
+    if let Some(human_id) = &proposal.human_id {
+        let alias = StorableString(human_id.clone());
+        if human_id.len() > MAX_HUMAN_ID_LEN as usize
+            || HUMAN_ID_MAP.with(|h| h.borrow().contains_key(&alias))
+        {
+            return Err(VoteError::InvalidHumanId);
+        }
+        HUMAN_ID_MAP.with(|h| h.borrow_mut().insert(alias, key));
+    }
+    index_description(key, &proposal);
+    index_deadline(key, &proposal);
+    index_group(key, &proposal);
+    register_tags(&proposal.tags);
+    adjust_active_count(false, proposal.is_active);
+    refresh_status(key, &proposal);
+    record_tally(key, &proposal);
+    log_event(key, EventKind::Created { is_active: proposal.is_active });
+    PROPOSAL_MAP.with(|p| p.borrow_mut().insert(key, proposal));
+    certify_tally(key);
+    Ok(())
+}
+
+fn release_human_id(proposal: &Proposal) {
+    if let Some(human_id) = &proposal.human_id {
+        HUMAN_ID_MAP.with(|h| h.borrow_mut().remove(&StorableString(human_id.clone())));
+    }
+}
+
+#[ic_cdk_macros::update]
+fn edit_proposal(key: u64, proposal: CreateProposal) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    validate_description(&proposal.description)?;
+    validate_quorum(proposal.quorum_pct)?;
+    validate_tags(&proposal.tags)?;
+    validate_attachments(&proposal.attachments)?;
+    validate_execution(&proposal.execution)?;
+    PROPOSAL_MAP.with(|p| {
+        let old_proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        if env::caller() != old_proposal.owner {            return Err(VoteError::Unauthorized);
+        }
+        if old_proposal.lock_on_first_vote && old_proposal.voter_count() > 0 {
+            return Err(VoteError::ProposalLocked);
+        }
+        if let Some(tags) = &proposal.tags {
+            check_tag_limit(tags)?;
+        }
+        // Voters approved a specific call; it cannot be swapped under them.
+        if proposal.execution.is_some() && old_proposal.voter_count() > 0 {
+            return Err(VoteError::ProposalLocked);
+        }
+        // Nor can voters who were promised privacy be made public afterwards.
+        if old_proposal.private_voters
+            && proposal.private_voters == Some(false)
+            && old_proposal.voter_count() > 0
+        {
+            return Err(VoteError::ProposalLocked);
+        }
+        if proposal.is_active && !old_proposal.is_active {
+            check_active_limit()?;
+        }
+        let old_tags = old_proposal.tags.clone();
+        let invalidate_votes = config().invalidate_votes_on_edit
+            && old_proposal.voter_count() > 0
+            && description_hash(&proposal.description) != description_hash(&old_proposal.description);
+        let mut value = Proposal {
+            description: proposal.description,
+            is_active: proposal.is_active,
+            owner: env::caller(),
+            private_voters: proposal.private_voters.unwrap_or(old_proposal.private_voters),
+            binary: proposal.binary.unwrap_or(old_proposal.binary),
+            quorum_pct: proposal.quorum_pct.or(old_proposal.quorum_pct),
+            updated_at: env::time(),
+            owner_vote_counts: proposal.owner_vote_counts.unwrap_or(old_proposal.owner_vote_counts),
+            tags: proposal.tags.unwrap_or_else(|| old_proposal.tags.clone()),
+            secret_hash: match proposal.secret.as_deref() {
+                Some(secret) => secret_hash(secret),
+                None => old_proposal.secret_hash,
+            },
+            attachments: proposal.attachments.unwrap_or_else(|| old_proposal.attachments.clone()),
+            listed: proposal.listed.unwrap_or(old_proposal.listed),
+            lock_on_first_vote: proposal
+                .lock_on_first_vote
+                .unwrap_or(old_proposal.lock_on_first_vote),
+            execution: proposal.execution.or_else(|| old_proposal.execution.clone()),
+            deadline: proposal.deadline.or(old_proposal.deadline),
+            ..old_proposal.clone()
+        };
+        check_encoded_size(&value)?;
+        adjust_active_count(old_proposal.is_active, value.is_active);
+        unindex_description(key, &old_proposal);
+        unindex_deadline(key, &old_proposal);
+        log_event(key, EventKind::Edited { is_active: value.is_active });
+        if value.description != old_proposal.description {
+            record_revision(key, &value);
+        }
+        if invalidate_votes {
+            value.voted.clear();
+            value.anonymous_ballots = 0;
+            value.set_tally_at(Tally::default(), env::time());
+            VOTE_MAP.with(|v| remove_scope(&mut v.borrow_mut(), key));
+            VOTE_TIMELINE.with(|t| remove_sequence(&mut t.borrow_mut(), key));
+            clear_nullifiers(key);
+            record_tally(key, &value);
+            log_event(key, EventKind::VotesInvalidated);
+        }
+        index_description(key, &value);
+        index_deadline(key, &value);
+        refresh_status(key, &value);
+        unregister_tags(&old_tags);
+        register_tags(&value.tags);
+        let res = p.borrow_mut().insert(key, value);
+        // Dropped votes and a change of `listed` both change the certified leaf.
+        certify_tally(key);
+        match res {
+            Some(_) => Ok(()),
+            None => Err(VoteError::UpdateError),
+        }
+    })
+}
+
+fn record_revision(key: u64, proposal: &Proposal) {
+    DESCRIPTION_HISTORY.with(|h| {
+        let mut history = h.borrow_mut();
+        let rev = next_sequence(&history, key);
+        let revision = Revision {
+            description: proposal.description.clone(),
+            timestamp: proposal.updated_at,
+        };
+        history.insert((key, rev), revision);
+        let keep = config().max_description_revisions.max(1) as usize;
+        let revisions: Vec<_> = history.range((key, 0)..=(key, u64::MAX)).map(|(k, _)| k).collect();
+        for entry in revisions.iter().take(revisions.len().saturating_sub(keep)) {
+            history.remove(entry);
+        }
+    });
+}
+
+// Revisions still stored; revision numbers keep counting past dropped ones.
+#[ic_cdk_macros::query]
+fn history_count(key: u64) -> u32 {
+    DESCRIPTION_HISTORY.with(|h| h.borrow().range((key, 0)..=(key, u64::MAX)).count() as u32)
+}
+
+// Line-based diff from one stored revision to another; `None` if either is unknown.
+#[ic_cdk_macros::query]
+fn get_description_diff(key: u64, from_rev: u64, to_rev: u64) -> Option<Vec<DiffChunk>> {
+    let (from, to) = DESCRIPTION_HISTORY.with(|h| {
+        let history = h.borrow();
+        (history.get(&(key, from_rev)), history.get(&(key, to_rev)))
+    });
+    Some(diff_lines(&from?.description, &to?.description))
+}
+
+// Longest-common-subsequence diff. Descriptions are bounded by MAX_VALUE_SIZE,
+// so the quadratic table stays small.
+fn diff_lines(from: &str, to: &str) -> Vec<DiffChunk> {
+    let old: Vec<&str> = from.lines().collect();
+    let new: Vec<&str> = to.lines().collect();
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut chunks = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            chunks.push(DiffChunk::Unchanged(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            chunks.push(DiffChunk::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            chunks.push(DiffChunk::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    chunks
+}
+
+// Oldest first. Scans the whole log, which is meant for audits rather than hot paths.
+#[ic_cdk_macros::query]
+fn get_proposal_events(key: u64, offset: u64, limit: u64) -> Vec<Event> {
+    let proposal = PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .or_else(|| ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)));
+    let view = ballot_view(key, proposal.as_ref());
+    EVENT_LOG.with(|l| {
+        l.borrow()
+            .iter()
+            .map(|(_, event)| event)
+            .filter(|event| event.proposal_key == key)
+            .filter_map(|event| event.public_view(view))
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .collect()
+    })
+}
+
+// Admins audit the raw log; everyone else sees ballots only as far as the
+// lagged, revealed counters go, without voters where they are private.
+fn ballot_view(key: u64, proposal: Option<&Proposal>) -> BallotView {
+    if is_admin(&env::caller()) {
+        return BallotView {
+            audit: true,
+            counts_hidden: false,
+            private_voters: false,
+            cutoff: u64::MAX,
+        };
+    }
+    let lagged = proposal.map(|proposal| lagged_view(key, proposal.clone()));
+    BallotView {
+        audit: false,
+        counts_hidden: lagged.as_ref().is_some_and(counts_hidden),
+        // Nothing tells whether the voters of a deleted proposal were private.
+        private_voters: proposal.is_none_or(|proposal| proposal.private_voters),
+        cutoff: tally_cutoff(),
+    }
+}
+
+// Newest first across all proposals, leaving out those of unlisted ones.
+#[ic_cdk_macros::query]
+fn activity_feed(offset: u64, limit: u64) -> Vec<Event> {
+    let limit = limit.min(MAX_PAGE_SIZE) as usize;
+    let mut visibility = HashMap::new();
+    // `None` for unlisted proposals, whose events are left out entirely.
+    let mut visibility_of = |key: u64| {
+        *visibility.entry(key).or_insert_with(|| {
+            let proposal = PROPOSAL_MAP
+                .with(|p| read_entry(&p.borrow(), key))
+                .or_else(|| ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)));
+            proposal
+                .as_ref()
+                .is_none_or(|proposal| proposal.listed)
+                .then(|| ballot_view(key, proposal.as_ref()))
+        })
+    };
+    EVENT_LOG.with(|l| {
+        let log = l.borrow();
+        let last = match log.last_key_value() {
+            Some((seq, _)) => seq,
+            None => return vec![],
+        };
+        // Sequence numbers are dense, so walking them backwards visits every event.
+        (0..=last)
+            .rev()
+            .filter_map(|seq| log.get(&seq))
+            .filter_map(|event| {
+                let view = visibility_of(event.proposal_key)?;
+                event.public_view(view)
+            })
+            .skip(offset as usize)
+            .take(limit)
+            .collect()
+    })
+}
+
+// Debugging aid: the proposal as its event stream says it should be. Events
+// carry no descriptions or settings, so those are taken from the stored value;
+// activity, voters and counters come from the events alone.
+#[ic_cdk_macros::query]
+fn replay_events(key: u64) -> Result<Proposal, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    let mut proposal = PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .or_else(|| ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)))
+        .ok_or(VoteError::NoProposal)?;
+    let mut tally = Tally::default();
+    let mut cast: HashMap<candid::Principal, Tally> = HashMap::new();
+    EVENT_LOG.with(|l| {
+        for (_, event) in l.borrow().iter() {
+            if event.proposal_key != key {
+                continue;
+            }
+            match event.kind {
+                EventKind::Created { is_active } => {
+                    proposal.is_active = is_active;
+                    tally = Tally::default();
+                    cast.clear();
+                    proposal.voted.clear();
+                }
+                EventKind::Edited { is_active } => proposal.is_active = is_active,
+                EventKind::Voted { choice, weight } => {
+                    tally.add(choice, weight);
+                    cast.entry(event.caller).or_default().add(choice, weight);
+                    if !proposal.voted.contains(&event.caller) {
+                        proposal.voted.push(event.caller);
+                    }
+                }
+                EventKind::VoteCancelled => {
+                    if let Some(retracted) = cast.remove(&event.caller) {
+                        tally.subtract(&retracted);
+                    }
+                    proposal.voted.retain(|v| *v != event.caller);
+                }
+                EventKind::VotesInvalidated => {
+                    tally = Tally::default();
+                    cast.clear();
+                    proposal.voted.clear();
+                }
+                EventKind::Ended => proposal.is_active = false,
+                EventKind::Activated => proposal.is_active = true,
+            }
+        }
+    });
+    proposal.set_tally(tally);
+    Ok(proposal)
+}
+
+// Whether the outcome survives every ballot still possible going to any single
+// option. Only proposals with an eligibility list have a known number of those
+// left, and only unweighted ones count each as one vote.
+fn result_locked(key: u64, proposal: &Proposal) -> bool {
+    if proposal.weighted {
+        return false;
+    }
+    let status = compute_status(key, proposal);
+    if status == ProposalStatus::Undecided {
+        return false;
+    }
+    let allowance = config().votes_per_principal;
+    let (eligible, remaining) = ELIGIBLE_MAP.with(|e| {
+        e.borrow()
+            .range(proposal_scope(key))
+            .take_while(|((k, _), _)| *k == key)
+            .fold((0u64, 0u64), |(eligible, remaining), (voter, _)| {
+                let used = VOTE_MAP.with(|v| v.borrow().get(&voter)).map_or(0, |r| r.ballots);
+                (eligible + 1, remaining + allowance.saturating_sub(used) as u64)
+            })
+    });
+    if eligible == 0 {
+        return false;
+    }
+    [VoteTypes::Approve, VoteTypes::Reject, VoteTypes::Pass]
+        .into_iter()
+        .filter(|choice| !(proposal.binary && matches!(choice, VoteTypes::Pass)))
+        .all(|choice| {
+            let mut worst_case = proposal.clone();
+            let mut tally = worst_case.tally();
+            tally.add(choice, remaining);
+            worst_case.set_tally(tally);
+            compute_status(key, &worst_case) == status
+        })
+}
+
+// Principals carry no subaccount of their own, so which ones share an identity
+// is registered by the admin; unregistered principals are their own base.
+fn base_principal(who: candid::Principal) -> candid::Principal {
+    if !config().normalize_principals {
+        return who;
+    }
+    PRINCIPAL_ALIASES
+        .with(|a| a.borrow().get(&StorablePrincipal(who)))
+        .map_or(who, |base| base.0)
+}
+
+// `None` unlinks `alias` again.
+#[ic_cdk_macros::update]
+fn set_principal_alias(
+    alias: candid::Principal,
+    base: Option<candid::Principal>,
+) -> Result<(), VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    PRINCIPAL_ALIASES.with(|a| {
+        let mut aliases = a.borrow_mut();
+        match base {
+            Some(base) => aliases.insert(StorablePrincipal(alias), StorablePrincipal(base)),
+            None => aliases.remove(&StorablePrincipal(alias)),
+        }
+    });
+    Ok(())
+}
+
+// First-seen time of `who`, recording the current time on its first interaction.
+// Rejected calls still count, since returning an error does not roll state back.
+fn mark_seen(who: candid::Principal) -> u64 {
+    FIRST_SEEN.with(|f| {
+        let mut first_seen = f.borrow_mut();
+        match first_seen.get(&StorablePrincipal(who)) {
+            Some(time) => time,
+            None => {
+                let now = env::time();
+                first_seen.insert(StorablePrincipal(who), now);
+                now
+            }
+        }
+    })
+}
+
+fn record_ballot(key: u64, choice: VoteTypes, weight: u64, timestamp: u64) {
+    VOTE_TIMELINE.with(|t| {
+        let mut timeline = t.borrow_mut();
+        let seq = next_sequence(&timeline, key);
+        if seq < MAX_TIMELINE_LEN {
+            timeline.insert((key, seq), TimelineEntry { timestamp, choice, weight });
+        }
+    });
+}
+
+// (timestamp, choice) of each recorded ballot, oldest first. Empty while
+// counts are hidden.
+#[ic_cdk_macros::query]
+fn get_vote_timeline(key: u64, offset: u64, limit: u64) -> Vec<(u64, VoteTypes)> {
+    if !timeline_visible(key) {
+        return vec![];
+    }
+    let cutoff = tally_cutoff();
+    VOTE_TIMELINE.with(|t| {
+        t.borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .filter(|(_, entry)| entry.timestamp <= cutoff)
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(_, entry)| (entry.timestamp, entry.choice))
+            .collect()
+    })
+}
+
+// The vote timeline summed into consecutive windows of `bucket_ns`, each keyed
+// by its start time. Windows without ballots are skipped rather than zero-filled.
+#[ic_cdk_macros::query]
+fn get_tally_buckets(key: u64, bucket_ns: u64) -> Vec<(u64, Tally)> {
+    if bucket_ns == 0 || !timeline_visible(key) {
+        return vec![];
+    }
+    let cutoff = tally_cutoff();
+    let mut buckets: Vec<(u64, Tally)> = Vec::new();
+    VOTE_TIMELINE.with(|t| {
+        let timeline = t.borrow();
+        let visible = timeline
+            .range((key, 0)..=(key, u64::MAX))
+            .take_while(|(_, entry)| entry.timestamp <= cutoff);
+        for (_, entry) in visible {
+            let start = entry.timestamp - entry.timestamp % bucket_ns;
+            match buckets.last_mut() {
+                Some((last, tally)) if *last == start => tally.add(entry.choice, entry.weight),
+                _ => {
+                    let mut tally = Tally::default();
+                    tally.add(entry.choice, entry.weight);
+                    buckets.push((start, tally));
+                }
+            }
+        }
+    });
+    buckets
+}
+
+fn timeline_visible(key: u64) -> bool {
+    PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .or_else(|| ARCHIVE_MAP.with(|a| read_entry(&a.borrow(), key)))
+        .is_some_and(|proposal| !counts_hidden(&lagged_view(key, proposal)))
+}
+
+fn log_event(key: u64, kind: EventKind) {
+    log_event_by(key, env::caller(), kind)
+}
+
+fn log_event_by(key: u64, caller: candid::Principal, kind: EventKind) {
+    append_event(Event {
+        proposal_key: key,
+        caller,
+        timestamp: env::time(),
+        kind,
+        private_voters: None,
+    })
+}
+
+fn log_ballot(key: u64, proposal: &Proposal, kind: EventKind) {
+    append_event(Event {
+        proposal_key: key,
+        caller: env::caller(),
+        timestamp: env::time(),
+        kind,
+        private_voters: Some(proposal.private_voters),
+    })
+}
+
+fn append_event(event: Event) {
+    EVENT_LOG.with(|l| {
+        let mut log = l.borrow_mut();
+        let seq = log.last_key_value().map_or(0, |(seq, _)| seq + 1);
+        log.insert(seq, event);
+    });
+}
+
+
+#[ic_cdk_macros::update]
+fn end_proposal(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        if env::caller() != proposal.owner {
+            return Err(VoteError::Unauthorized);
+        }
+        let status = close_proposal(key, &mut proposal, env::caller());
+        let res = p.borrow_mut().insert(key, proposal);
+        match res {
+            Some(_) => {
+                notify_finalized(key, status);
+                Ok(())
+            }
+            None => Err(VoteError::UpdateError),
+        }
+    })
+}
+
+fn close_proposal(key: u64, proposal: &mut Proposal, by: candid::Principal) -> ProposalStatus {
+    adjust_active_count(proposal.is_active, false);
+    proposal.is_active = false;
+    proposal.updated_at = env::time();
+    unindex_description(key, proposal);
+    unindex_deadline(key, proposal);
+    let status = refresh_status(key, proposal);
+    log_event_by(key, by, EventKind::Ended);
+    status
+}
+
+// Ends every active proposal whose deadline has passed, as if its owner had.
+// The `Ended` events name no caller since nobody ended them.
+fn close_expired_proposals() -> u64 {
+    let now = env::time();
+    let due: Vec<(u64, u64)> =
+        DEADLINES.with(|d| d.borrow().range(..=(now, u64::MAX)).copied().collect());
+    let mut closed = 0;
+    for (deadline, key) in due {
+        DEADLINES.with(|d| d.borrow_mut().remove(&(deadline, key)));
+        let mut proposal = match PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)) {
+            Some(proposal) if proposal.is_active && proposal.is_expired(now) => proposal,
+            _ => continue,
+        };
+        let status = close_proposal(key, &mut proposal, candid::Principal::anonymous());
+        PROPOSAL_MAP.with(|p| p.borrow_mut().insert(key, proposal));
+        notify_finalized(key, status);
+        closed += 1;
+    }
+    closed
+}
+
+// Closes expired proposals right away instead of at the next timer run.
+#[ic_cdk_macros::update]
+fn sweep_expired_proposals() -> Result<u64, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    Ok(close_expired_proposals())
+}
+
+// Timers do not survive an upgrade, so this runs from `post_upgrade` as well.
+fn schedule_expiry_sweep() {
+    let interval = std::time::Duration::from_nanos(EXPIRY_SWEEP_INTERVAL_NS);
+    env::set_timer_interval(interval, || {
+        close_expired_proposals();
+    });
+}
+
+#[ic_cdk_macros::update]
+fn activate_proposal(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        if env::caller() != proposal.owner {
+            return Err(VoteError::Unauthorized);
+        }
+        if proposal.is_active {
+            return Ok(());
+        }
+        check_active_limit()?;
+        adjust_active_count(false, true);
+        proposal.is_active = true;
+        proposal.updated_at = env::time();
+        index_description(key, &proposal);
+        index_deadline(key, &proposal);
+        refresh_status(key, &proposal);
+        log_event(key, EventKind::Activated);
+        p.borrow_mut().insert(key, proposal);
+        Ok(())
+    })
+}
+
+#[ic_cdk_macros::update]
+fn set_quorum(key: u64, quorum: u64) -> Result<(), VoteError> {
+    update_voting_rules(key, |proposal| proposal.quorum = Some(quorum))
+}
+
+#[ic_cdk_macros::update]
+fn set_threshold(key: u64, pct: u8) -> Result<(), VoteError> {
+    if pct == 0 || pct > 100 {
+        return Err(VoteError::InvalidThreshold);
+    }
+    update_voting_rules(key, |proposal| proposal.threshold_pct = Some(pct))
+}
+
+#[ic_cdk_macros::update]
+fn set_min_winning_votes(key: u64, min: Option<u64>) -> Result<(), VoteError> {
+    update_voting_rules(key, |proposal| proposal.min_winning_votes = min)
+}
+
+// Rules only change while voting is open so a closed outcome cannot be rewritten.
+fn update_voting_rules(key: u64, f: impl FnOnce(&mut Proposal)) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        if env::caller() != proposal.owner {
+            return Err(VoteError::Unauthorized);
+        }
+        if !proposal.is_active {
+            return Err(VoteError::ProposalNotActive);
+        }
+        f(&mut proposal);
+        proposal.updated_at = env::time();
+        refresh_status(key, &proposal);
+        log_event(key, EventKind::Edited { is_active: true });
+        p.borrow_mut().insert(key, proposal);
+        Ok(())
+    })
+}
+
+// ACTIVE_COUNT still counts expired proposals that were not closed yet, so a
+// count at the limit is confirmed against the map before refusing.
+fn check_active_limit() -> Result<(), VoteError> {
+    match config().max_active_proposals {
+        Some(max) if ACTIVE_COUNT.with(|c| *c.borrow().get()) >= max && open_count() >= max => {
+            Err(VoteError::ActiveLimitReached)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn open_count() -> u64 {
+    let now = env::time();
+    PROPOSAL_MAP.with(|p| p.borrow().iter().filter(|(_, p)| p.is_open(now)).count() as u64)
+}
+
+fn adjust_active_count(was_active: bool, is_active: bool) {
+    ACTIVE_COUNT.with(|c| {
+        let count = *c.borrow().get();
+        let count = match (was_active, is_active) {
+            (false, true) => count + 1,
+            (true, false) => count.saturating_sub(1),
+            _ => return,
+        };
+        c.borrow_mut().set(count).expect("failed to store the active count");
+    });
+}
+
+// Best effort: a missing or failing subscriber must never block finalization.
+fn notify_finalized(key: u64, status: ProposalStatus) {
+    if let Some(subscriber) = config().subscriber {
+        env::notify(subscriber, "proposal_finalized", (key, status));
+    }
+}
+
+// Held by an async vote from before its first await until it returns, so a
+// second call from the same voter cannot slip in between the checks and the
+// ballot. Dropping it frees the pair on every exit path.
+struct InFlightVote((candid::Principal, u64));
+
+impl InFlightVote {
+    fn acquire(who: candid::Principal, key: u64) -> Result<Self, VoteError> {
+        if VOTES_IN_FLIGHT.with(|v| v.borrow_mut().insert((who, key))) {
+            Ok(InFlightVote((who, key)))
+        } else {
+            Err(VoteError::VoteInProgress)
+        }
+    }
+}
+
+impl Drop for InFlightVote {
+    fn drop(&mut self) {
+        VOTES_IN_FLIGHT.with(|v| v.borrow_mut().remove(&self.0));
+    }
+}
+
+#[ic_cdk_macros::update]
+async fn vote(
+    key: u64,
+    choice: VoteTypes,
+    secret: Option<String>,
+    idempotency_key: Option<Vec<u8>>,
+) -> Result<(), VoteError> {
+    let caller = env::caller();
+    let _in_flight = InFlightVote::acquire(base_principal(caller), key)?;
+    fetch_neuron_power(key, base_principal(caller)).await?;
+    let idempotency_key = match idempotency_key {
+        Some(bytes) => bytes,
+        None => return apply_vote(key, caller, choice, secret.as_deref()).map(|_| ()),
+    };
+    // The proposal key is hashed in so that one client key cannot cover two proposals.
+    let mut hasher = Sha256::new();
+    hasher.update(key.to_be_bytes());
+    hasher.update(&idempotency_key);
+    let key_hash: [u8; 32] = hasher.finalize().into();
+    let voter = StorablePrincipal(caller);
+    if let Some(previous) = IDEMPOTENCY_MAP.with(|i| i.borrow().get(&voter)) {
+        if previous.key_hash == key_hash {
+            return previous.result;
+        }
+    }
+    let result = apply_vote(key, caller, choice, secret.as_deref()).map(|_| ());
+    let entry = IdempotentVote {
+        key_hash,
+        result: result.clone(),
+    };
+    IDEMPOTENCY_MAP.with(|i| i.borrow_mut().insert(voter, entry));
+    result
+}
+
+#[ic_cdk_macros::update]
+async fn vote_with_receipt(
+    key: u64,
+    choice: VoteTypes,
+    secret: Option<String>,
+) -> Result<VoteReceipt, VoteError> {
+    let caller = env::caller();
+    let _in_flight = InFlightVote::acquire(base_principal(caller), key)?;
+    fetch_neuron_power(key, base_principal(caller)).await?;
+    apply_vote(key, caller, choice, secret.as_deref())
+}
+
+// Private-tally proposals only. Only a nullifier is kept to refuse a second
+// ballot; no voter list, vote record, cached power or event names the caller.
+// The nullifier hides nobody from someone who already suspects a principal.
+#[ic_cdk_macros::update]
+async fn vote_private(
+    key: u64,
+    choice: VoteTypes,
+    secret: Option<String>,
+) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    let caller = env::caller();
+    check_not_anonymous(caller)?;
+    let caller = base_principal(caller);
+    let _in_flight = InFlightVote::acquire(caller, key)?;
+    let power = fetch_power(key, caller).await?;
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        let nullifier = nullifier_of(key, caller);
+        if !proposal.private_tally {
+            return Err(VoteError::WrongVotingMode);
+        } else if NULLIFIERS.with(|n| n.borrow().contains_key(&nullifier)) {
+            return Err(VoteError::AlreadyVoted);
+        }
+        // Marking the caller as seen here would date their private ballot.
+        let first_seen = FIRST_SEEN
+            .with(|f| f.borrow().get(&StorablePrincipal(caller)))
+            .unwrap_or_else(env::time);
+        check_ballot(key, &proposal, caller, choice, secret.as_deref(), first_seen)?;
+        let weight = power.unwrap_or_else(|| vote_weight(key, &proposal, caller));
+        if weight == 0 {
+            return Err(VoteError::NoVotingPower);
+        }
+        NULLIFIERS.with(|n| n.borrow_mut().insert(nullifier, ()));
+        let now = env::time();
+        let mut tally = proposal.tally();
+        tally.add(choice, weight);
+        proposal.set_tally_at(tally, now);
+        proposal.anonymous_ballots += 1;
+        proposal.updated_at = now;
+        let status = refresh_status(key, &proposal);
+        record_tally(key, &proposal);
+        let finalized =
+            auto_finalize(key, &mut proposal, status, candid::Principal::anonymous());
+        p.borrow_mut().insert(key, proposal);
+        certify_tally(key);
+        if finalized {
+            notify_finalized(key, status);
+        }
+        Ok(())
+    })
+}
+
+// First step of a two-step vote: nothing is counted until `confirm_vote`.
+#[ic_cdk_macros::update]
+fn prepare_vote(key: u64, choice: VoteTypes) -> Result<VoteToken, VoteError> {
+    check_not_frozen()?;
+    let caller = env::caller();
+    check_not_anonymous(caller)?;
+    if !PROPOSAL_MAP.with(|p| p.borrow().contains_key(&key)) {
+        return Err(VoteError::NoProposal);
+    }
+    let now = env::time();
+    let mut hasher = Sha256::new();
+    hasher.update(caller.as_slice());
+    hasher.update(key.to_be_bytes());
+    hasher.update([choice as u8]);
+    hasher.update(now.to_be_bytes());
+    let token: VoteToken = hasher.finalize().into();
+    let pending = PendingVote {
+        voter: caller,
+        proposal_key: key,
+        choice,
+        expires_at: now.saturating_add(config().vote_confirmation_window_ns),
+    };
+    PENDING_VOTES.with(|p| {
+        let mut pending_votes = p.borrow_mut();
+        // Abandoned tokens are dropped here, as nothing else would ever remove them.
+        let expired: Vec<VoteToken> = pending_votes
+            .iter()
+            .filter(|(_, pending)| pending.expires_at <= now)
+            .map(|(token, _)| token)
+            .collect();
+        for token in &expired {
+            pending_votes.remove(token);
+        }
+        pending_votes.insert(token, pending);
+    });
+    Ok(token)
+}
+
+// Only the principal that prepared the vote can confirm it, and only once.
+#[ic_cdk_macros::update]
+async fn confirm_vote(token: VoteToken) -> Result<(), VoteError> {
+    let caller = env::caller();
+    let pending = match PENDING_VOTES.with(|p| p.borrow().get(&token)) {
+        Some(pending) if pending.voter == caller => pending,
+        _ => return Err(VoteError::InvalidVoteToken),
+    };
+    let _in_flight = InFlightVote::acquire(base_principal(caller), pending.proposal_key)?;
+    PENDING_VOTES.with(|p| p.borrow_mut().remove(&token));
+    if pending.expires_at <= env::time() {
+        return Err(VoteError::VoteTokenExpired);
+    }
+    fetch_neuron_power(pending.proposal_key, base_principal(caller)).await?;
+    apply_vote(pending.proposal_key, caller, pending.choice, None).map(|_| ())
+}
+
+#[ic_cdk_macros::update]
+fn vote_many(keys: Vec<u64>, choice: VoteTypes) -> Vec<Result<(), VoteError>> {
+    if keys.len() > MAX_BATCH_SIZE {
+        env::trap("too many keys in a single batch");
+    }
+    let caller = env::caller();
+    keys.into_iter()
+        .map(|key| apply_vote(key, caller, choice, None).map(|_| ()))
+        .collect()
+}
+
+// Everything a ballot must pass besides the voting mode and the voter's own
+// earlier ballots on this proposal.
+fn check_ballot(
+    key: u64,
+    proposal: &Proposal,
+    caller: candid::Principal,
+    choice: VoteTypes,
+    secret: Option<&str>,
+    first_seen: u64,
+) -> Result<(), VoteError> {
+    if voted_in_group(key, proposal, caller) {
+        Err(VoteError::AlreadyVoted)
+    } else if !proposal.is_active || proposal.is_expired(env::time()) {
+        Err(VoteError::ProposalNotActive)
+    } else if proposal.secret_hash.is_some()
+        && secret.and_then(secret_hash) != proposal.secret_hash
+    {
+        Err(VoteError::Unauthorized)
+    } else if proposal.binary && matches!(choice, VoteTypes::Pass) {
+        Err(VoteError::InvalidOption)
+    } else if !is_eligible(key, caller) {
+        Err(VoteError::NotEligible)
+    } else if config().require_registration
+        && !VOTER_MAP.with(|v| v.borrow().contains_key(&StorablePrincipal(caller)))
+    {
+        Err(VoteError::NotRegistered)
+    } else if env::time().saturating_sub(first_seen) < config().min_account_age_ns {
+        Err(VoteError::AccountTooNew)
+    } else if config().early_lock && result_locked(key, proposal) {
+        Err(VoteError::ResultLocked)
+    } else {
+        Ok(())
+    }
+}
+
+// The vote that makes the proposal pass closes it on the spot. `by` is who the
+// Ended event names.
+fn auto_finalize(
+    key: u64,
+    proposal: &mut Proposal,
+    status: ProposalStatus,
+    by: candid::Principal,
+) -> bool {
+    let finalized = config().auto_finalize && status == ProposalStatus::Approved;
+    if finalized {
+        adjust_active_count(true, false);
+        proposal.is_active = false;
+        unindex_description(key, proposal);
+        unindex_deadline(key, proposal);
+        log_event_by(key, by, EventKind::Ended);
+    }
+    finalized
+}
+
+fn apply_vote(
+    key: u64,
+    caller: candid::Principal,
+    choice: VoteTypes,
+    secret: Option<&str>,
+) -> Result<VoteReceipt, VoteError> {
+    check_not_frozen()?;
+    check_not_anonymous(caller)?;
+    let caller = base_principal(caller);
+    let first_seen = mark_seen(caller);
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        let allowance = config().votes_per_principal;
+        let voter = (key, StorablePrincipal(caller));
+        let previous = VOTE_MAP.with(|v| v.borrow().get(&voter));
+        // Imported proposals list their voters without vote records; each of
+        // them has cast at least one ballot.
+        let ballots = match &previous {
+            Some(record) => record.ballots,
+            None => u32::from(proposal.voted.contains(&caller)),
+        };
+        if ballots >= allowance {
+            return Err(if allowance <= 1 {
+                VoteError::AlreadyVoted
+            } else {
+                VoteError::AllowanceExhausted
+            });
+        } else if proposal.private_tally {
+            return Err(VoteError::WrongVotingMode);
+        }
+        check_ballot(key, &proposal, caller, choice, secret, first_seen)?;
+        let weight = vote_weight(key, &proposal, caller);
+        if weight == 0 {
+            return Err(VoteError::NoVotingPower);
+        }
+        let mut tally = proposal.tally();
+        tally.add(choice, weight);
+        proposal.set_tally_at(tally, env::time());
+        if !proposal.voted.contains(&caller) {
+            proposal.voted.push(caller);
+            // Every new voter grows the stored proposal.
+            check_encoded_size(&proposal)?;
+        }
+        let timestamp = env::time();
+        proposal.updated_at = timestamp;
+        let mut cast = previous.map_or_else(Tally::default, |record| record.cast);
+        cast.add(choice, weight);
+        let record = VoteRecord {
+            choice,
+            timestamp,
+            cast,
+            ballots: ballots + 1,
+        };
+        VOTE_MAP.with(|v| v.borrow_mut().insert(voter, record));
+        let status = refresh_status(key, &proposal);
+        record_tally(key, &proposal);
+        log_ballot(key, &proposal, EventKind::Voted { choice, weight });
+        if config().record_vote_timeline {
+            record_ballot(key, choice, weight, timestamp);
+        }
+        let finalized = auto_finalize(key, &mut proposal, status, caller);
+        // The history entry for this vote is in, so the receipt shows what `get_tally` would.
+        let tally_after = reported_tally(key, proposal.clone()).unwrap_or_default();
+        let res = p.borrow_mut().insert(key, proposal);
+        certify_tally(key);
+        if finalized {
+            notify_finalized(key, status);
+        }
+        match res {
+            Some(_) => Ok(VoteReceipt {
+                proposal_key: key,
+                choice,
+                tally_after,
+                timestamp,
+            }),
+            None => Err(VoteError::VoteFailed),
+        }
+    })
+}
+
+// Retracts every ballot the caller cast on the proposal, not only the latest.
+#[ic_cdk_macros::update]
+fn cancel_recent_vote(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    let caller = base_principal(env::caller());
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        if !proposal.is_open(env::time()) {
+            return Err(VoteError::ProposalNotActive);
+        }
+        let voter = (key, StorablePrincipal(caller));
+        let record = match VOTE_MAP.with(|v| v.borrow().get(&voter)) {
+            Some(value) => value,
+            None => return Err(VoteError::NotVoted),
+        };
+        let elapsed = env::time().saturating_sub(record.timestamp);
+        if elapsed > config().vote_cancellation_window_ns {
+            return Err(VoteError::CancellationWindowClosed);
+        }
+        let mut tally = proposal.tally();
+        tally.subtract(&record.cast);
+        proposal.set_tally_at(tally, env::time());
+        proposal.voted.retain(|v| *v != caller);
+        proposal.updated_at = env::time();
+        VOTE_MAP.with(|v| v.borrow_mut().remove(&voter));
+        refresh_status(key, &proposal);
+        record_tally(key, &proposal);
+        log_ballot(key, &proposal, EventKind::VoteCancelled);
+        p.borrow_mut().insert(key, proposal);
+        certify_tally(key);
+        Ok(())
+    })
+}
+
+// Recurring polls: closes the current round under the next round number (from 0)
+// and starts a fresh one in which everybody can vote again.
+#[ic_cdk_macros::update]
+fn snapshot_and_reset(key: u64) -> Result<TallySnapshot, VoteError> {
+    check_not_frozen()?;
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        if env::caller() != proposal.owner {
+            return Err(VoteError::Unauthorized);
+        }
+        if !proposal.is_open(env::time()) {
+            return Err(VoteError::ProposalNotActive);
+        }
+        let snapshot = TallySnapshot::of(&proposal);
+        ROUNDS.with(|r| {
+            let mut rounds = r.borrow_mut();
+            let round = next_sequence(&rounds, key);
+            rounds.insert((key, round), snapshot);
+        });
+        let now = env::time();
+        proposal.voted.clear();
+        proposal.anonymous_ballots = 0;
+        proposal.set_tally_at(Tally::default(), now);
+        proposal.updated_at = now;
+        VOTE_MAP.with(|v| remove_scope(&mut v.borrow_mut(), key));
+        VOTE_TIMELINE.with(|t| remove_sequence(&mut t.borrow_mut(), key));
+        clear_nullifiers(key);
+        refresh_status(key, &proposal);
+        record_tally(key, &proposal);
+        log_event(key, EventKind::VotesInvalidated);
+        p.borrow_mut().insert(key, proposal);
+        certify_tally(key);
+        Ok(snapshot)
+    })
+}
+
+#[ic_cdk_macros::query]
+fn get_round(key: u64, round: u64) -> Option<TallySnapshot> {
+    ROUNDS.with(|r| r.borrow().get(&(key, round)))
+}
+
+// Anyone may trigger the call of an approved proposal, and it runs at most once:
+// the proposal is marked executed before the call and unmarked if it fails.
+#[ic_cdk_macros::update]
+async fn execute_proposal(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    let mut proposal = match PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)) {
+        Some(value) => value,
+        None => return Err(VoteError::NoProposal),
+    };
+    let payload = match proposal.execution.clone() {
+        Some(payload) => payload,
+        None => return Err(VoteError::InvalidExecution),
+    };
+    if proposal.executed {
+        return Err(VoteError::AlreadyExecuted);
+    }
+    // Voting must be over: an early Approved can still flip before the deadline.
+    if proposal.is_active && !proposal.is_expired(env::time()) {
+        return Err(VoteError::ProposalActive);
+    }
+    if visible_status(key) != Some(ProposalStatus::Approved) {
+        return Err(VoteError::NotApproved);
+    }
+    proposal.executed = true;
+    PROPOSAL_MAP.with(|p| p.borrow_mut().insert(key, proposal));
+    let result = env::call_raw(payload.target, &payload.method, &payload.args).await;
+    if result.is_err() {
+        PROPOSAL_MAP.with(|p| {
+            let mut proposals = p.borrow_mut();
+            if let Some(mut proposal) = read_entry(&proposals, key) {
+                proposal.executed = false;
+                proposals.insert(key, proposal);
+            }
+        });
+        return Err(VoteError::ExecutionFailed);
+    }
+    Ok(())
+}
+
+// Self-heal: rebuilds the counters from the per-voter records, which are authoritative.
+#[ic_cdk_macros::update]
+fn recount(key: u64) -> Result<Tally, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        let mut tally = Tally::default();
+        VOTE_MAP.with(|v| {
+            for (_, record) in v
+                .borrow()
+                .range(proposal_scope(key))
+                .take_while(|((k, _), _)| *k == key)
+            {
+                tally.add(VoteTypes::Approve, record.cast.approve);
+                tally.add(VoteTypes::Reject, record.cast.reject);
+                tally.add(VoteTypes::Pass, record.cast.pass);
+            }
+        });
+        proposal.set_tally_at(tally, env::time());
+        refresh_status(key, &proposal);
+        record_tally(key, &proposal);
+        p.borrow_mut().insert(key, proposal);
+        certify_tally(key);
+        Ok(tally)
+    })
+}
+
+#[ic_cdk_macros::update]
+fn archive_proposal(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    PROPOSAL_MAP.with(|p| {
+        let proposal = match read_entry(&p.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        let caller = env::caller();
+        if caller != proposal.owner && !is_admin(&caller) {
+            return Err(VoteError::Unauthorized);
+        }
+        if proposal.is_active {
+            return Err(VoteError::ProposalActive);
+        }
+        p.borrow_mut().remove(&key);
+        evict_status(key);
+        unindex_group(key, &proposal);
+        ARCHIVE_MAP.with(|a| a.borrow_mut().insert(key, proposal));
+        certify_tally(key);
+        Ok(())
+    })
+}
+
+#[ic_cdk_macros::update]
+fn restore_proposal(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    ARCHIVE_MAP.with(|a| {
+        let proposal = match read_entry(&a.borrow(), key) {
+            Some(value) => value,
+            None => return Err(VoteError::NoProposal),
+        };
+        let caller = env::caller();
+        if caller != proposal.owner && !is_admin(&caller) {
+            return Err(VoteError::Unauthorized);
+        }
+        if PROPOSAL_MAP.with(|p| p.borrow().contains_key(&key)) {
+            return Err(VoteError::ProposalAlreadyExists);
+        }
+        a.borrow_mut().remove(&key);
+        index_description(key, &proposal);
+        index_deadline(key, &proposal);
+        index_group(key, &proposal);
+        refresh_status(key, &proposal);
+        PROPOSAL_MAP.with(|p| p.borrow_mut().insert(key, proposal));
+        certify_tally(key);
+        Ok(())
+    })
+}
+
+#[ic_cdk_macros::update]
+fn set_display_name(name: String) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    mark_seen(env::caller());
+    let name = validate_display_name(name)?;
+    DISPLAY_NAME_MAP.with(|d| {
+        d.borrow_mut()
+            .insert(StorablePrincipal(env::caller()), StorableString(name))
+    });
+    Ok(())
+}
+
+fn validate_display_name(name: String) -> Result<String, VoteError> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.len() > MAX_DISPLAY_NAME_LEN as usize {
+        return Err(VoteError::InvalidDisplayName);
+    }
+    Ok(name)
+}
+
+// Registering again only updates the profile; `registered_at` is kept.
+#[ic_cdk_macros::update]
+fn register_voter(profile: VoterProfile) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    mark_seen(env::caller());
+    let display_name = validate_display_name(profile.display_name)?;
+    let caller = StorablePrincipal(env::caller());
+    VOTER_MAP.with(|v| {
+        let registered_at = v
+            .borrow()
+            .get(&caller)
+            .map_or_else(env::time, |voter| voter.registered_at);
+        v.borrow_mut().insert(
+            caller,
+            Voter {
+                registered_at,
+                display_name,
+            },
+        )
+    });
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn add_eligible_voter(key: u64, who: candid::Principal) -> Result<(), VoteError> {
+    update_eligibility(key, |e| {
+        e.insert((key, StorablePrincipal(who)), ());
+    })
+}
+
+#[ic_cdk_macros::update]
+fn remove_eligible_voter(key: u64, who: candid::Principal) -> Result<(), VoteError> {
+    update_eligibility(key, |e| {
+        e.remove(&(key, StorablePrincipal(who)));
+    })
+}
+
+// Replaces the whole whitelist in one call; an empty list opens the proposal to everyone.
+#[ic_cdk_macros::update]
+fn set_eligible_voters(key: u64, voters: Vec<candid::Principal>) -> Result<(), VoteError> {
+    if voters.len() > MAX_ELIGIBLE_BATCH_SIZE {
+        env::trap("too many voters in a single batch");
+    }
+    update_eligibility(key, |e| {
+        remove_scope(e, key);
+        for who in voters {
+            e.insert((key, StorablePrincipal(who)), ());
+        }
+    })
+}
+
+fn update_eligibility(
+    key: u64,
+    f: impl FnOnce(&mut StableBTreeMap<(u64, StorablePrincipal), (), Memory>),
+) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    let proposal = match PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)) {
+        Some(value) => value,
+        None => return Err(VoteError::NoProposal),
+    };
+    if env::caller() != proposal.owner {
+        return Err(VoteError::Unauthorized);
+    }
+    ELIGIBLE_MAP.with(|e| f(&mut e.borrow_mut()));
+    refresh_status(key, &proposal);
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn add_comment(key: u64, body: String) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    let caller = env::caller();
+    check_not_anonymous(caller)?;
+    mark_seen(caller);
+    if body.trim().is_empty() || body.len() > MAX_COMMENT_LEN as usize {
+        return Err(VoteError::InvalidComment);
+    }
+    if !PROPOSAL_MAP.with(|p| p.borrow().contains_key(&key)) {
+        return Err(VoteError::NoProposal);
+    }
+    let voted = VOTE_MAP.with(|v| v.borrow().contains_key(&(key, StorablePrincipal(caller))));
+    let allowed = match config().comment_policy {
+        CommentPolicy::Open => true,
+        CommentPolicy::VotersOnly => voted,
+        CommentPolicy::NonVotersOnly => !voted,
+    };
+    if !allowed {
+        return Err(VoteError::Unauthorized);
+    }
+    let comment = Comment {
+        author: caller,
+        body,
+        timestamp: env::time(),
+    };
+    COMMENT_MAP.with(|c| {
+        let mut comments = c.borrow_mut();
+        let seq = next_sequence(&comments, key);
+        comments.insert((key, seq), comment);
+    });
+    Ok(())
+}
+
+// Oldest first.
+#[ic_cdk_macros::query]
+fn get_comments(key: u64, offset: u64, limit: u64) -> Vec<Comment> {
+    COMMENT_MAP.with(|c| {
+        c.borrow()
+            .range((key, 0)..=(key, u64::MAX))
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|(_, comment)| comment)
+            .collect()
+    })
+}
+
+#[ic_cdk_macros::update]
+fn subscribe(key: u64) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    mark_seen(env::caller());
+    if !PROPOSAL_MAP.with(|p| p.borrow().contains_key(&key)) {
+        return Err(VoteError::NoProposal);
+    }
+    let subscriber = (key, StorablePrincipal(env::caller()));
+    SUBSCRIPTION_MAP.with(|s| s.borrow_mut().insert(subscriber, ()));
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn unsubscribe(key: u64) {
+    if config().frozen {
+        env::trap("canister is frozen");
+    }
+    let subscriber = (key, StorablePrincipal(env::caller()));
+    SUBSCRIPTION_MAP.with(|s| s.borrow_mut().remove(&subscriber));
+}
+
+#[ic_cdk_macros::query]
+fn get_subscriber_count(key: u64) -> u64 {
+    SUBSCRIPTION_MAP.with(|s| {
+        s.borrow()
+            .range(proposal_scope(key))
+            .take_while(|((k, _), _)| *k == key)
+            .count() as u64
+    })
+}
+
+#[ic_cdk_macros::query]
+fn get_subscribers(key: u64, offset: u64, limit: u64) -> Vec<candid::Principal> {
+    SUBSCRIPTION_MAP.with(|s| {
+        s.borrow()
+            .range(proposal_scope(key))
+            .take_while(|((k, _), _)| *k == key)
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .map(|((_, subscriber), _)| subscriber.0)
+            .collect()
+    })
+}
+
+// Only the hot map is purged; archived proposals are kept on purpose.
+#[ic_cdk_macros::update]
+fn purge_closed_older_than(age_ns: u64) -> Result<u64, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    let cutoff = env::time().saturating_sub(age_ns);
+    let keys: Vec<u64> = PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| !proposal.is_active && proposal.updated_at < cutoff)
+            .map(|(key, _)| key)
+            .collect()
+    });
+    for key in &keys {
+        if let Some(proposal) = PROPOSAL_MAP.with(|p| p.borrow_mut().remove(key)) {
+            unindex_group(*key, &proposal);
+            release_human_id(&proposal);
+            unregister_tags(&proposal.tags);
+        }
+        remove_side_entries(*key);
+        certify_tally(*key);
+    }
+    Ok(keys.len() as u64)
+}
+
+#[ic_cdk_macros::query]
+fn verify_integrity() -> Result<IntegrityReport, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    let mut report = IntegrityReport::default();
+    PROPOSAL_MAP.with(|p| {
+        for (key, proposal) in p.borrow().iter() {
+            report.scanned += 1;
+            // Every voter holds one ballot unless `votes_per_principal` allows more.
+            let ballots: u64 = VOTE_MAP.with(|v| {
+                v.borrow()
+                    .range(proposal_scope(key))
+                    .take_while(|((k, _), _)| *k == key)
+                    .map(|(_, record)| record.ballots as u64)
+                    .sum::<u64>()
+            }) + proposal.anonymous_ballots as u64;
+            let tally = proposal.tally();
+            let counted = tally.counted(false);
+            if !proposal.weighted
+                && (counted != ballots || ballots < proposal.voted.len() as u64)
+            {
+                report.tally_mismatch.push(key);
+            }
+            if Encode!(&proposal).map_or(true, |bytes| bytes.len() > MAX_VALUE_SIZE as usize) {
+                report.oversized.push(key);
+            }
+        }
+    });
+    PARTICIPATION_PERCENTAGE_MAP.with(|p| {
+        for (key, _) in p.borrow().iter() {
+            let known = PROPOSAL_MAP.with(|p| p.borrow().contains_key(&key))
+                || ARCHIVE_MAP.with(|a| a.borrow().contains_key(&key));
+            if !known {
+                report.orphaned_participation.push(key);
+            }
+        }
+    });
+    Ok(report)
+}
+
+// Positional like `vote_many`. Removes live or archived proposals for good,
+// together with everything stored about them besides the event log.
+#[ic_cdk_macros::update]
+fn bulk_delete_proposals(keys: Vec<u64>) -> Vec<Result<(), VoteError>> {
+    if keys.len() > MAX_BATCH_SIZE {
+        env::trap("too many keys in a single batch");
+    }
+    let admin = is_admin(&env::caller());
+    keys
+        .into_iter()
+        .map(|key| if admin { delete_proposal(key) } else { Err(VoteError::Unauthorized) })
+        .collect()
+}
+
+fn delete_proposal(key: u64) -> Result<(), VoteError> {
+    let proposal = match PROPOSAL_MAP.with(|p| p.borrow_mut().remove(&key)) {
+        Some(proposal) => {
+            adjust_active_count(proposal.is_active, false);
+            unindex_description(key, &proposal);
+            unindex_deadline(key, &proposal);
+            unindex_group(key, &proposal);
+            proposal
+        }
+        None => ARCHIVE_MAP
+            .with(|a| a.borrow_mut().remove(&key))
+            .ok_or(VoteError::NoProposal)?,
+    };
+    release_human_id(&proposal);
+    unregister_tags(&proposal.tags);
+    remove_side_entries(key);
+    certify_tally(key);
+    Ok(())
+}
+
+// Hands every live proposal of a departing owner to `to`. Archived proposals
+// keep their original owner.
+#[ic_cdk_macros::update]
+fn transfer_all_proposals(from: candid::Principal, to: candid::Principal) -> Result<u64, VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    let now = env::time();
+    let keys: Vec<u64> = PROPOSAL_MAP.with(|p| {
+        p.borrow()
+            .iter()
+            .filter(|(_, proposal)| proposal.owner == from)
+            .map(|(key, _)| key)
+            .collect()
+    });
+    PROPOSAL_MAP.with(|p| {
+        let mut proposals = p.borrow_mut();
+        for key in &keys {
+            if let Some(mut proposal) = proposals.get(key) {
+                unindex_group(*key, &proposal);
+                proposal.owner = to;
+                index_group(*key, &proposal);
+                proposal.updated_at = now;
+                // Whose ballot `owner_vote_counts` excludes has changed.
+                refresh_status(*key, &proposal);
+                proposals.insert(*key, proposal);
+            }
+        }
+    });
+    Ok(keys.len() as u64)
+}
+
+// Drops everything stored about a proposal outside of PROPOSAL_MAP/ARCHIVE_MAP.
+fn remove_side_entries(key: u64) {
+    evict_status(key);
+    PARTICIPATION_PERCENTAGE_MAP.with(|p| p.borrow_mut().remove(&key));
+    VOTE_MAP.with(|v| remove_scope(&mut v.borrow_mut(), key));
+    ELIGIBLE_MAP.with(|e| remove_scope(&mut e.borrow_mut(), key));
+    WEIGHT_SNAPSHOT.with(|w| remove_scope(&mut w.borrow_mut(), key));
+    NEURON_POWER.with(|n| remove_scope(&mut n.borrow_mut(), key));
+    SUBSCRIPTION_MAP.with(|s| remove_scope(&mut s.borrow_mut(), key));
+    clear_tally_history(key);
+    COMMENT_MAP.with(|c| remove_sequence(&mut c.borrow_mut(), key));
+    VOTE_TIMELINE.with(|t| remove_sequence(&mut t.borrow_mut(), key));
+    DESCRIPTION_HISTORY.with(|h| remove_sequence(&mut h.borrow_mut(), key));
+    ROUNDS.with(|r| remove_sequence(&mut r.borrow_mut(), key));
+    clear_nullifiers(key);
+}
+
+fn nullifier_of(key: u64, who: candid::Principal) -> (u64, [u8; 32]) {
+    let mut hasher = Sha256::new();
+    hasher.update(who.as_slice());
+    hasher.update(key.to_be_bytes());
+    (key, hasher.finalize().into())
+}
+
+// Whether `who` voted on another live proposal of `proposal`'s vote group.
+// Groups are per owner, so nobody can join a poll of their own to another
+// owner's motion and lock its voters out.
+fn voted_in_group(key: u64, proposal: &Proposal, who: candid::Principal) -> bool {
+    let group = match proposal.vote_group_id {
+        Some(group) => group,
+        None => return false,
+    };
+    let siblings: Vec<u64> = VOTE_GROUPS.with(|g| {
+        g.borrow()
+            .range((proposal.owner, group, 0)..=(proposal.owner, group, u64::MAX))
+            .map(|(_, _, sibling)| *sibling)
+            .filter(|sibling| *sibling != key)
+            .collect()
+    });
+    siblings.into_iter().any(|sibling| {
+        VOTE_MAP.with(|v| v.borrow().contains_key(&(sibling, StorablePrincipal(who))))
+            || NULLIFIERS.with(|n| n.borrow().contains_key(&nullifier_of(sibling, who)))
+    })
+}
+
+fn index_group(key: u64, proposal: &Proposal) {
+    if let Some(group) = proposal.vote_group_id {
+        VOTE_GROUPS.with(|g| g.borrow_mut().insert((proposal.owner, group, key)));
+    }
+}
+
+fn unindex_group(key: u64, proposal: &Proposal) {
+    if let Some(group) = proposal.vote_group_id {
+        VOTE_GROUPS.with(|g| g.borrow_mut().remove(&(proposal.owner, group, key)));
+    }
+}
+
+fn clear_nullifiers(key: u64) {
+    NULLIFIERS.with(|n| {
+        let mut nullifiers = n.borrow_mut();
+        let entries: Vec<_> = nullifiers
+            .range((key, [0; 32])..=(key, [u8::MAX; 32]))
+            .map(|(k, _)| k)
+            .collect();
+        for entry in &entries {
+            nullifiers.remove(entry);
+        }
+    });
+}
+
+// Sequence number for the next entry of `key` in a (key, sequence) map.
+fn next_sequence<V: BoundedStorable>(map: &StableBTreeMap<(u64, u64), V, Memory>, key: u64) -> u64 {
+    match map.iter_upper_bound(&(key, u64::MAX)).next() {
+        Some(((k, last), _)) if k == key => last + 1,
+        _ => 0,
+    }
+}
+
+fn clear_tally_history(key: u64) {
+    TALLY_HISTORY.with(|h| remove_sequence(&mut h.borrow_mut(), key));
+}
+
+// `remove_scope` for maps keyed by (key, sequence number or time).
+fn remove_sequence<V: BoundedStorable>(map: &mut StableBTreeMap<(u64, u64), V, Memory>, key: u64) {
+    let entries: Vec<_> = map.range((key, 0)..=(key, u64::MAX)).map(|(k, _)| k).collect();
+    for entry in &entries {
+        map.remove(entry);
+    }
+}
+
+// Removes every `(key, principal)` entry of one proposal from a side map.
+fn remove_scope<V: BoundedStorable>(
+    map: &mut StableBTreeMap<(u64, StorablePrincipal), V, Memory>,
+    key: u64,
+) {
+    let entries: Vec<_> = map
+        .range(proposal_scope(key))
+        .take_while(|((k, _), _)| *k == key)
+        .map(|(entry, _)| entry)
+        .collect();
+    for entry in &entries {
+        map.remove(entry);
+    }
+}
+
+fn snapshot_weights(key: u64) {
+    VOTER_WEIGHTS.with(|weights| {
+        WEIGHT_SNAPSHOT.with(|snapshot| {
+            let mut snapshot = snapshot.borrow_mut();
+            for (who, weight) in weights.borrow().iter() {
+                snapshot.insert((key, who), weight);
+            }
+        })
+    });
+}
+
+// Unweighted proposals count every vote once; weighted ones only know the
+// principals that had a weight when the proposal was created.
+fn vote_weight(key: u64, proposal: &Proposal, who: candid::Principal) -> u64 {
+    if !proposal.weighted {
+        return 1;
+    }
+    let voter = (key, StorablePrincipal(who));
+    if config().neuron_source.is_some() {
+        return NEURON_POWER.with(|n| n.borrow().get(&voter)).unwrap_or(0);
+    }
+    WEIGHT_SNAPSHOT.with(|w| w.borrow().get(&voter)).unwrap_or(0)
+}
+
+// Weight of the caller's next ballot on `key`; zero for unknown proposals and,
+// with a neuron source, until the caller's power has been fetched by a vote.
+#[ic_cdk_macros::query]
+fn effective_weight(key: u64) -> u64 {
+    let caller = base_principal(env::caller());
+    PROPOSAL_MAP
+        .with(|p| read_entry(&p.borrow(), key))
+        .map_or(0, |proposal| vote_weight(key, &proposal, caller))
+}
+
+// Fetches the caller's power once per weighted proposal; later ballots and
+// `vote_many` use the cached value. Zero power is cached as well.
+async fn fetch_neuron_power(key: u64, who: candid::Principal) -> Result<(), VoteError> {
+    let voter = (key, StorablePrincipal(who));
+    if NEURON_POWER.with(|n| n.borrow().contains_key(&voter)) {
+        return Ok(());
+    }
+    if let Some(power) = fetch_power(key, who).await? {
+        NEURON_POWER.with(|n| n.borrow_mut().insert(voter, power));
+    }
+    Ok(())
+}
+
+// `who`'s power from the neuron source, `None` unless one is configured and
+// the proposal is weighted.
+async fn fetch_power(key: u64, who: candid::Principal) -> Result<Option<u64>, VoteError> {
+    let source = match config().neuron_source {
+        Some(source) => source,
+        None => return Ok(None),
+    };
+    let weighted = PROPOSAL_MAP.with(|p| read_entry(&p.borrow(), key)).is_some_and(|p| p.weighted);
+    if !weighted {
+        return Ok(None);
+    }
+    let (power,): (u64,) = env::call(source, "get_voting_power", (who,))
+        .await
+        .map_err(|_| VoteError::VoteFailed)?;
+    Ok(Some(power))
+}
+
+#[ic_cdk_macros::update]
+fn set_voter_weight(who: candid::Principal, weight: u64) -> Result<(), VoteError> {
+    if !is_admin(&env::caller()) {
+        return Err(VoteError::Unauthorized);
+    }
+    VOTER_WEIGHTS.with(|w| {
+        let mut w = w.borrow_mut();
+        if weight == 0 {
+            w.remove(&StorablePrincipal(who));
+        } else {
+            w.insert(StorablePrincipal(who), weight);
+        }
+    });
+    Ok(())
+}
+
+#[ic_cdk_macros::update]
+fn delegate(to: candid::Principal) -> Result<(), VoteError> {
+    set_delegation(to, None)
+}
+
+#[ic_cdk_macros::update]
+fn delegate_until(to: candid::Principal, expiry_ns: u64) -> Result<(), VoteError> {
+    set_delegation(to, Some(expiry_ns))
+}
+
+#[ic_cdk_macros::update]
+fn undelegate() {
+    if config().frozen {
+        env::trap("canister is frozen");
+    }
+    let delegator = StorablePrincipal(env::caller());
+    if let Some(old) = DELEGATION_MAP.with(|d| d.borrow_mut().remove(&delegator)) {
+        DELEGATOR_INDEX.with(|i| i.borrow_mut().remove(&(StorablePrincipal(old.to), delegator)));
+    }
+}
+
+fn set_delegation(to: candid::Principal, expiry_ns: Option<u64>) -> Result<(), VoteError> {
+    check_not_frozen()?;
+    let caller = env::caller();
+    mark_seen(caller);
+    if to == caller {
+        return Err(VoteError::InvalidDelegation);
+    }
+    let delegator = StorablePrincipal(caller);
+    let delegation = Delegation { to, expiry_ns };
+    let old = DELEGATION_MAP.with(|d| d.borrow_mut().insert(delegator, delegation));
+    DELEGATOR_INDEX.with(|i| {
+        let mut index = i.borrow_mut();
+        if let Some(old) = old {
+            index.remove(&(StorablePrincipal(old.to), delegator));
+        }
+        index.insert((StorablePrincipal(to), delegator), ());
+    });
+    Ok(())
+}
+
+#[ic_cdk_macros::query]
+fn my_delegate() -> Option<candid::Principal> {
+    DELEGATION_MAP
+        .with(|d| d.borrow().get(&StorablePrincipal(env::caller())))
+        .filter(|delegation| delegation.is_live(env::time()))
+        .map(|delegation| delegation.to)
+}
+
+// Principals whose live delegation points at the caller.
+#[ic_cdk_macros::query]
+fn my_delegators() -> Vec<candid::Principal> {
+    let delegate = StorablePrincipal(env::caller());
+    let start = (delegate, StorablePrincipal(candid::Principal::management_canister()));
+    let now = env::time();
+    DELEGATOR_INDEX.with(|i| {
+        i.borrow()
+            .range(start..)
+            .take_while(|((to, _), _)| *to == delegate)
+            .map(|((_, delegator), _)| delegator)
+            .filter(|delegator| {
+                DELEGATION_MAP
+                    .with(|d| d.borrow().get(delegator))
+                    .is_some_and(|delegation| delegation.is_live(now))
+            })
+            .map(|delegator| delegator.0)
+            .collect()
+    })
+}
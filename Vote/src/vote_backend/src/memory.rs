@@ -0,0 +1,70 @@
+// Every stable memory the canister allocates from MEMORY_MANAGER. Ids are part of
+// the stable layout: never renumber or reuse one, only append.
+use ic_stable_structures::memory_manager::MemoryId;
+
+macro_rules! memory_ids {
+    ($($name:ident = $id:literal,)*) => {
+        $(pub(crate) const $name: MemoryId = MemoryId::new($id);)*
+        pub(crate) const ALLOCATED: &[u8] = &[$($id),*];
+    };
+}
+
+memory_ids! {
+    // Proposal maps from before values could be MAX_VALUE_SIZE; emptied on upgrade.
+    LEGACY_PROPOSAL_MAP = 0,
+    PARTICIPATION_PERCENTAGE_MAP = 1,
+    CONFIG = 2,
+    LEGACY_ARCHIVE_MAP = 3,
+    VOTE_MAP = 4,
+    DISPLAY_NAME_MAP = 5,
+    ELIGIBLE_MAP = 6,
+    DESCRIPTION_INDEX = 7,
+    VOTER_MAP = 8,
+    ACTIVE_COUNT = 9,
+    DELEGATION_MAP = 10,
+    // Weight maps from before weights were u64; emptied on upgrade.
+    LEGACY_VOTER_WEIGHTS = 11,
+    LEGACY_WEIGHT_SNAPSHOT = 12,
+    SUBSCRIPTION_MAP = 13,
+    TALLY_HISTORY = 14,
+    HUMAN_ID_MAP = 15,
+    HUMAN_ID_COUNTERS = 16,
+    EVENT_LOG = 17,
+    DELEGATOR_INDEX = 18,
+    IDEMPOTENCY_MAP = 19,
+    COMMENT_MAP = 20,
+    VOTE_TIMELINE = 21,
+    DESCRIPTION_HISTORY = 22,
+    FIRST_SEEN = 23,
+    RESERVATIONS = 24,
+    LEGACY_NEURON_POWER = 25,
+    TAG_REGISTRY = 26,
+    ROUNDS = 27,
+    PRINCIPAL_ALIASES = 28,
+    PENDING_VOTES = 29,
+    NULLIFIERS = 30,
+    LAST_CREATE = 31,
+    PROPOSAL_MAP = 32,
+    ARCHIVE_MAP = 33,
+    VOTER_WEIGHTS = 34,
+    WEIGHT_SNAPSHOT = 35,
+    NEURON_POWER = 36,
+}
+
+pub(crate) const fn all_unique(ids: &[u8]) -> bool {
+    let mut i = 0;
+    while i < ids.len() {
+        let mut j = i + 1;
+        while j < ids.len() {
+            if ids[i] == ids[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+// Reusing an id fails the build instead of silently sharing memory between two maps.
+const _: () = assert!(all_unique(ALLOCATED), "a MemoryId is allocated twice");
@@ -0,0 +1,110 @@
+// Sparse Merkle tree over the certified tallies with one leaf per u64 key, so a
+// change rehashes the 64 nodes above its leaf instead of every proposal. Only
+// subtrees that are not empty are kept. The tree lives on the heap and is
+// rebuilt after an upgrade, just like the certified data it produces.
+//
+//   leaf = sha256(0x00 ++ key ++ approve ++ reject ++ pass), all big-endian
+//   node = sha256(0x01 ++ left ++ right); at height h bit h of the key picks the side
+//
+// A missing leaf hashes to 32 zero bytes, and an empty subtree to the node over
+// two empty subtrees one level down.
+use super::Tally;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+pub(crate) type Hash = [u8; 32];
+
+const HEIGHT: u32 = 64;
+
+thread_local! {
+    // (height, key >> height) -> hash of that subtree.
+    static NODES: RefCell<HashMap<(u32, u64), Hash>> = RefCell::new(HashMap::new());
+    // Hash of an empty subtree of each height.
+    static EMPTY: Vec<Hash> = {
+        let mut hashes = vec![[0; 32]];
+        for height in 0..HEIGHT as usize {
+            hashes.push(node_hash(&hashes[height], &hashes[height]));
+        }
+        hashes
+    };
+}
+
+pub(crate) fn leaf_hash(key: u64, tally: &Tally) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0]);
+    hasher.update(key.to_be_bytes());
+    hasher.update(tally.approve.to_be_bytes());
+    hasher.update(tally.reject.to_be_bytes());
+    hasher.update(tally.pass.to_be_bytes());
+    hasher.finalize().into()
+}
+
+pub(crate) fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn prefix(key: u64, height: u32) -> u64 {
+    key.checked_shr(height).unwrap_or(0)
+}
+
+// Sets the leaf of `key`, or removes it for `None`, and returns the new root.
+pub(crate) fn update(key: u64, leaf: Option<Hash>) -> Hash {
+    NODES.with(|n| {
+        EMPTY.with(|empty| {
+            let mut nodes = n.borrow_mut();
+            let mut hash = leaf.unwrap_or(empty[0]);
+            for height in 0..HEIGHT {
+                let at = prefix(key, height);
+                store(&mut nodes, (height, at), hash, &empty[height as usize]);
+                let sibling = nodes
+                    .get(&(height, at ^ 1))
+                    .copied()
+                    .unwrap_or(empty[height as usize]);
+                hash = if at & 1 == 0 {
+                    node_hash(&hash, &sibling)
+                } else {
+                    node_hash(&sibling, &hash)
+                };
+            }
+            store(&mut nodes, (HEIGHT, 0), hash, &empty[HEIGHT as usize]);
+            hash
+        })
+    })
+}
+
+fn store(nodes: &mut HashMap<(u32, u64), Hash>, at: (u32, u64), hash: Hash, empty: &Hash) {
+    if hash == *empty {
+        nodes.remove(&at);
+    } else {
+        nodes.insert(at, hash);
+    }
+}
+
+pub(crate) fn root() -> Hash {
+    let root = NODES.with(|n| n.borrow().get(&(HEIGHT, 0)).copied());
+    root.unwrap_or_else(|| EMPTY.with(|empty| empty[HEIGHT as usize]))
+}
+
+// Sibling hashes on the path from the leaf of `key` to the root, lowest first.
+pub(crate) fn witness(key: u64) -> Vec<Hash> {
+    NODES.with(|n| {
+        EMPTY.with(|empty| {
+            let nodes = n.borrow();
+            (0..HEIGHT)
+                .map(|height| {
+                    let sibling = (height, prefix(key, height) ^ 1);
+                    nodes.get(&sibling).copied().unwrap_or(empty[height as usize])
+                })
+                .collect()
+        })
+    })
+}
+
+pub(crate) fn clear() {
+    NODES.with(|n| n.borrow_mut().clear());
+}
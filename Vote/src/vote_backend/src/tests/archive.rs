@@ -0,0 +1,119 @@
+use super::*;
+
+fn archive(key: u64, owner: Principal) {
+    as_user(owner, || {
+        end_proposal(key).unwrap();
+        archive_proposal(key).unwrap();
+    });
+}
+
+#[test]
+fn archived_key_cannot_be_recreated() {
+    setup();
+    create(1, user(1), "Fund the docs sprint");
+    archive(1, user(1));
+    assert_eq!(
+        as_user(user(2), || create_proposal(1, proposal("Take over key one"))).err(),
+        Some(VoteError::ProposalAlreadyExists)
+    );
+    assert_eq!(get_archived(1).unwrap().description, "Fund the docs sprint");
+    assert!(get_proposal(1).is_none());
+}
+
+#[test]
+fn restored_proposal_keeps_its_record() {
+    setup();
+    create(1, user(1), "Fund the docs sprint");
+    archive(1, user(1));
+    as_user(user(1), || restore_proposal(1)).unwrap();
+    assert_eq!(get_proposal(1).unwrap().description, "Fund the docs sprint");
+    assert!(get_archived(1).is_none());
+}
+
+fn closed(key: u64, description: &str) {
+    create(key, user(1), description);
+    cast(key, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(2), || add_comment(key, "Agreed".to_string())).unwrap();
+    as_user(user(1), || end_proposal(key)).unwrap();
+}
+
+#[test]
+fn purge_removes_only_old_closed_proposals() {
+    setup();
+    closed(1, "Old and closed");
+    create(2, user(1), "Old but open");
+    env::advance_time(1_000);
+    closed(3, "Recent and closed");
+    assert_eq!(as_user(user(1), || purge_closed_older_than(500)), Err(VoteError::Unauthorized));
+    assert_eq!(purge_closed_older_than(500), Ok(1));
+    assert!(get_proposal(1).is_none());
+    assert!(get_comments(1, 0, 10).is_empty());
+    assert!(VOTE_MAP.with(|v| v.borrow().get(&(1, StorablePrincipal(user(2))))).is_none());
+    assert!(get_proposal(2).is_some());
+    assert!(get_proposal(3).is_some());
+    assert_eq!(get_comments(3, 0, 10).len(), 1);
+}
+
+#[test]
+fn restore_requires_an_archived_key_that_is_not_live() {
+    setup();
+    create(1, user(1), "Fund the docs sprint");
+    assert_eq!(as_user(user(1), || restore_proposal(1)), Err(VoteError::NoProposal));
+    archive(1, user(1));
+    assert_eq!(as_user(user(2), || restore_proposal(1)), Err(VoteError::Unauthorized));
+    assert!(list_proposals(0, 10, false).is_empty());
+    restore_proposal(1).unwrap();
+    let listed: Vec<u64> = list_proposals(0, 10, false).into_iter().map(|(key, _)| key).collect();
+    assert_eq!(listed, vec![1]);
+}
+
+#[test]
+fn bulk_delete_reports_each_key_and_cleans_side_maps() {
+    setup();
+    create(1, user(1), "Fund the docs sprint");
+    create(2, user(1), "Fund the test sprint");
+    create(3, user(1), "Fund the sprint");
+    archive(3, user(1));
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(2), || subscribe(1)).unwrap();
+    as_user(user(3), || add_comment(1, "Needs a plan".to_string())).unwrap();
+    assert_eq!(
+        as_user(user(1), || bulk_delete_proposals(vec![1])),
+        vec![Err(VoteError::Unauthorized)]
+    );
+    assert_eq!(
+        bulk_delete_proposals(vec![1, 9, 3, 1]),
+        vec![Ok(()), Err(VoteError::NoProposal), Ok(()), Err(VoteError::NoProposal)]
+    );
+    assert!(get_proposal(1).is_none() && get_archived(3).is_none());
+    assert!(get_proposal(2).is_some());
+    assert!(VOTE_MAP.with(|v| v.borrow().is_empty()));
+    assert_eq!(get_subscriber_count(1), 0);
+    assert!(get_comments(1, 0, 10).is_empty());
+    assert!(STATUS_CACHE.with(|c| c.borrow().get(&1).is_none()));
+    assert_eq!(ACTIVE_COUNT.with(|c| *c.borrow().get()), 1);
+}
+
+fn stats(who: Principal) -> (u64, u64, u64, f64) {
+    let s = owner_stats(who);
+    (s.total_created, s.total_approved, s.total_rejected, s.avg_turnout)
+}
+
+#[test]
+fn owner_stats_cover_live_and_archived_proposals() {
+    setup();
+    assert_eq!(stats(user(1)), (0, 0, 0, 0.0));
+    let outcomes = [(1, VoteTypes::Approve), (2, VoteTypes::Approve), (3, VoteTypes::Reject)];
+    for (key, choice) in outcomes {
+        create(key, user(1), &format!("Fund sprint {key}"));
+        for voter in 10..15 {
+            cast(key, user(voter), choice).unwrap();
+        }
+    }
+    create(4, user(1), "Fund sprint 4");
+    cast(4, user(10), VoteTypes::Approve).unwrap();
+    create(5, user(2), "Fund sprint 5");
+    archive(2, user(1));
+    assert_eq!(stats(user(1)), (4, 2, 1, 16.0 / 4.0));
+    assert_eq!(stats(user(2)), (1, 0, 0, 0.0));
+}
@@ -0,0 +1,120 @@
+use super::*;
+
+// What a client does with a witness: hash the counters up to the root.
+fn verified_root(key: u64, witness: &TallyWitness) -> Vec<u8> {
+    let mut hash = tally_tree::leaf_hash(key, &witness.tally);
+    for (height, sibling) in witness.siblings.iter().enumerate() {
+        let sibling: tally_tree::Hash = sibling.as_slice().try_into().unwrap();
+        hash = if key.checked_shr(height as u32).unwrap_or(0) & 1 == 0 {
+            tally_tree::node_hash(&hash, &sibling)
+        } else {
+            tally_tree::node_hash(&sibling, &hash)
+        };
+    }
+    hash.to_vec()
+}
+
+fn rebuilt_root() -> Vec<u8> {
+    certify_all_tallies();
+    env::certified_data()
+}
+
+#[test]
+fn certified_data_changes_with_every_vote() {
+    setup();
+    create(1, user(1), "Plant more trees");
+    let before = env::certified_data();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    let after = env::certified_data();
+    assert_ne!(before, after);
+    assert_eq!(as_user(user(2), || cancel_recent_vote(1)), Ok(()));
+    assert_eq!(env::certified_data(), before);
+}
+
+#[test]
+fn witnesses_verify_against_the_certified_data() {
+    setup();
+    for key in [0, 1, 7, u64::MAX] {
+        create(key, user(1), &format!("Plant {key} trees"));
+    }
+    cast(7, user(2), VoteTypes::Reject).unwrap();
+    cast(u64::MAX, user(3), VoteTypes::Pass).unwrap();
+    for key in [0, 1, 7, u64::MAX] {
+        let witness = get_tally_witness(key).unwrap();
+        assert_eq!(verified_root(key, &witness), env::certified_data());
+    }
+    assert_eq!(get_tally_witness(7).unwrap().tally.reject, 1);
+    assert!(get_tally_witness(2).is_none());
+}
+
+#[test]
+fn incremental_updates_match_a_full_rebuild() {
+    setup();
+    for key in 1..=4 {
+        create(key, user(1), &format!("Plant {key} trees"));
+    }
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    bulk_delete_proposals(vec![3]).pop().unwrap().unwrap();
+    let incremental = env::certified_data();
+    assert_eq!(rebuilt_root(), incremental);
+    assert!(get_tally_witness(3).is_none());
+}
+
+#[test]
+fn removing_every_leaf_leaves_the_empty_root() {
+    let empty = tally_tree::root();
+    let leaf = tally_tree::leaf_hash(5, &Tally::default());
+    assert_ne!(tally_tree::update(5, Some(leaf)), empty);
+    assert_eq!(tally_tree::update(5, None), empty);
+}
+
+#[test]
+fn unlisted_proposals_are_left_out_of_the_certified_data() {
+    setup();
+    create(1, user(1), "Plant more trees");
+    let listed_only = env::certified_data();
+    let unlisted = CreateProposal {
+        listed: Some(false),
+        ..proposal("A poll shared by link")
+    };
+    as_user(user(1), || create_proposal(2, unlisted)).unwrap();
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(env::certified_data(), listed_only);
+    assert!(get_tally_witness(2).is_none());
+    let listed = CreateProposal {
+        listed: Some(true),
+        ..proposal("A poll shared by link")
+    };
+    as_user(user(1), || edit_proposal(2, listed)).unwrap();
+    let witness = get_tally_witness(2).unwrap();
+    assert_eq!(verified_root(2, &witness), env::certified_data());
+}
+
+#[test]
+fn hidden_counts_are_not_certified() {
+    setup();
+    set_min_reveal_voters(2).unwrap();
+    create(1, user(1), "Plant more trees");
+    let before = env::certified_data();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(env::certified_data(), before);
+    assert!(get_tally_witness(1).is_none());
+    set_min_reveal_voters(1).unwrap();
+    let witness = get_tally_witness(1).unwrap();
+    assert_eq!(verified_root(1, &witness), env::certified_data());
+}
+
+#[test]
+fn no_counters_are_certified_under_a_tally_delay() {
+    setup();
+    create(1, user(1), "Plant more trees");
+    set_tally_delay(1_000).unwrap();
+    let delayed = env::certified_data();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(env::certified_data(), delayed);
+    assert!(get_tally_witness(1).is_none());
+    set_tally_delay(0).unwrap();
+    let witness = get_tally_witness(1).unwrap();
+    assert_eq!(witness.tally.approve, 1);
+    assert_eq!(verified_root(1, &witness), env::certified_data());
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+// `user(2)` voted on proposal 1 and `user(3)` did not.
+fn comment_as(who: Principal, policy: CommentPolicy) -> Result<(), VoteError> {
+    setup();
+    set_comment_policy(policy).unwrap();
+    create(1, user(1), "Rename the channel");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    as_user(who, || add_comment(1, "Fine by me".to_string()))
+}
+
+#[test]
+fn open_comments_take_voters() {
+    assert_eq!(comment_as(user(2), CommentPolicy::Open), Ok(()));
+}
+
+#[test]
+fn open_comments_take_non_voters() {
+    assert_eq!(comment_as(user(3), CommentPolicy::Open), Ok(()));
+}
+
+#[test]
+fn voters_only_comments_take_voters() {
+    assert_eq!(comment_as(user(2), CommentPolicy::VotersOnly), Ok(()));
+    assert_eq!(get_comments(1, 0, 10)[0].author, user(2));
+}
+
+#[test]
+fn voters_only_comments_refuse_non_voters() {
+    let refused = comment_as(user(3), CommentPolicy::VotersOnly);
+    assert_eq!(refused, Err(VoteError::Unauthorized));
+    assert!(get_comments(1, 0, 10).is_empty());
+}
+
+#[test]
+fn non_voters_only_comments_refuse_voters() {
+    let refused = comment_as(user(2), CommentPolicy::NonVotersOnly);
+    assert_eq!(refused, Err(VoteError::Unauthorized));
+}
+
+#[test]
+fn non_voters_only_comments_take_non_voters() {
+    assert_eq!(comment_as(user(3), CommentPolicy::NonVotersOnly), Ok(()));
+}
+
+#[test]
+fn comments_are_listed_oldest_first() {
+    setup();
+    create(1, user(1), "Rename the channel");
+    for (n, body) in ["First", "Second", "Third"].into_iter().enumerate() {
+        env::advance_time(1);
+        as_user(user(n as u8 + 2), || add_comment(1, body.to_string())).unwrap();
+    }
+    let bodies: Vec<_> = get_comments(1, 1, 10).into_iter().map(|c| c.body).collect();
+    assert_eq!(bodies, vec!["Second", "Third"]);
+    let blank = as_user(user(2), || add_comment(1, "  ".to_string()));
+    assert_eq!(blank, Err(VoteError::InvalidComment));
+    let missing = as_user(user(2), || add_comment(2, "Hello".to_string()));
+    assert_eq!(missing, Err(VoteError::NoProposal));
+}
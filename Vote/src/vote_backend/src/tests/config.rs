@@ -0,0 +1,40 @@
+use super::*;
+
+#[test]
+fn config_starts_from_the_defaults_with_the_installer_as_admin() {
+    setup();
+    let config = get_config();
+    assert_eq!(config.admin, admin());
+    assert!(!config.require_registration && !config.frozen && !config.allow_anonymous);
+    assert_eq!(config.votes_per_principal, 1);
+    assert_eq!(config.max_active_proposals, None);
+    assert!(config.pass_is_turnout);
+    assert!(matches!(config.comment_policy, CommentPolicy::Open));
+}
+
+#[test]
+fn config_reflects_the_setters() {
+    setup();
+    set_require_registration(true).unwrap();
+    set_max_active_proposals(Some(7)).unwrap();
+    set_votes_per_principal(3).unwrap();
+    set_tally_delay(500).unwrap();
+    set_pow_difficulty(Some(4)).unwrap();
+    set_comment_policy(CommentPolicy::VotersOnly).unwrap();
+    set_tie_break(TieBreak::EarliestMajority).unwrap();
+    let config = get_config();
+    assert!(config.require_registration);
+    assert_eq!(config.max_active_proposals, Some(7));
+    assert_eq!(config.votes_per_principal, 3);
+    assert_eq!(config.tally_delay_ns, 500);
+    assert_eq!(config.pow_difficulty, Some(4));
+    assert!(matches!(config.comment_policy, CommentPolicy::VotersOnly));
+    assert!(config.tie_break == TieBreak::EarliestMajority);
+}
+
+#[test]
+fn only_controllers_change_the_config() {
+    setup();
+    assert_eq!(as_user(user(1), || set_tally_delay(500)), Err(VoteError::Unauthorized));
+    assert_eq!(get_config().tally_delay_ns, 0);
+}
@@ -0,0 +1,85 @@
+use super::*;
+
+const DELAY: u64 = 1_000;
+
+// One approval old enough to show and one rejection still inside the delay.
+fn one_visible_ballot() {
+    setup();
+    set_tally_delay(DELAY).unwrap();
+    set_record_vote_timeline(true).unwrap();
+    create(1, user(1), "Move the meetup online");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(DELAY / 2);
+    cast(1, user(3), VoteTypes::Reject).unwrap();
+    env::advance_time(DELAY * 3 / 5);
+}
+
+fn ballots(events: &[Event]) -> usize {
+    events.iter().filter(|event| matches!(event.kind, EventKind::Voted { .. })).count()
+}
+
+#[test]
+fn proposal_reads_lag_behind() {
+    one_visible_ballot();
+    let proposal = get_proposal(1).unwrap();
+    assert_eq!((proposal.approve, proposal.reject), (1, 0));
+    assert!(proposal.reached_at.reject <= env::time() - DELAY);
+    assert_eq!(list_proposals(0, 10, false)[0].1.reject, 0);
+    assert_eq!(get_voters(1, 0, 10), vec![user(2)]);
+    assert_eq!(get_proposal_metrics(1).unwrap().turnout, 1);
+}
+
+#[test]
+fn ballot_reads_lag_behind() {
+    one_visible_ballot();
+    assert!(get_voters_by_choice(1, VoteTypes::Reject).is_empty());
+    assert_eq!(get_vote_timeline(1, 0, 10), vec![(env::START_TIME, VoteTypes::Approve)]);
+    let buckets = get_tally_buckets(1, DELAY * 10);
+    assert_eq!(buckets.iter().map(|(_, tally)| tally.reject).sum::<u64>(), 0);
+    assert_eq!(ballots(&as_user(user(9), || get_proposal_events(1, 0, 10))), 1);
+    assert_eq!(ballots(&as_user(user(9), || activity_feed(0, 10))), 1);
+    assert_eq!(ballots(&get_proposal_events(1, 0, 10)), 2);
+}
+
+#[test]
+fn delayed_ballots_show_up_later() {
+    one_visible_ballot();
+    env::advance_time(DELAY);
+    assert_eq!(get_proposal(1).unwrap().reject, 1);
+    assert_eq!(get_voters_by_choice(1, VoteTypes::Reject), vec![user(3)]);
+    assert_eq!(get_vote_timeline(1, 0, 10).len(), 2);
+}
+
+#[test]
+fn widest_snapshot_fits_its_slot() {
+    let widest = TallySnapshot {
+        tally: Tally {
+            approve: u64::MAX,
+            reject: u64::MAX,
+            pass: u64::MAX,
+        },
+        voters: u32::MAX,
+        anonymous_ballots: Some(u32::MAX),
+    };
+    assert!(widest.to_bytes().len() <= TallySnapshot::MAX_SIZE as usize);
+}
+
+#[test]
+fn past_tallies_are_answered_within_the_retention() {
+    setup();
+    set_tally_history_retention(DELAY).unwrap();
+    let created = env::time();
+    create(1, user(1), "Move the meetup online");
+    env::advance_time(10);
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(10);
+    cast(1, user(3), VoteTypes::Reject).unwrap();
+    let counts = |time| get_tally_as_of(1, time).map(|t| (t.approve, t.reject, t.pass));
+    assert_eq!(counts(created - 1), None);
+    assert_eq!(counts(created + 5), Some((0, 0, 0)));
+    assert_eq!(counts(created + 15), Some((1, 0, 0)));
+    assert_eq!(counts(created + 20), Some((1, 1, 0)));
+    env::advance_time(DELAY);
+    assert_eq!(counts(created + 15), None);
+    assert_eq!(counts(env::time()), Some((1, 1, 0)));
+}
@@ -0,0 +1,83 @@
+use super::*;
+
+fn tally(approve: u64, reject: u64, pass: u64) -> Option<Tally> {
+    Some(Tally {
+        approve,
+        reject,
+        pass,
+    })
+}
+
+#[test]
+fn delegations_are_live_until_their_expiry() {
+    let open = Delegation {
+        to: user(2),
+        expiry_ns: None,
+    };
+    assert!(open.is_live(u64::MAX));
+    let bounded = Delegation {
+        to: user(2),
+        expiry_ns: Some(100),
+    };
+    assert!(bounded.is_live(99));
+    assert!(!bounded.is_live(100));
+}
+
+#[test]
+fn delegated_votes_count_until_the_delegation_expires() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    let expiry = env::time() + 1_000;
+    assert_eq!(as_user(user(3), || delegate_until(user(2), expiry)), Ok(()));
+    assert_eq!(as_user(user(4), || delegate(user(2))), Ok(()));
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    assert_eq!(get_delegated_tally(1), tally(0, 3, 0));
+    assert_eq!(as_user(user(3), my_delegate), Some(user(2)));
+    env::advance_time(1_000);
+    assert_eq!(get_delegated_tally(1), tally(0, 2, 0));
+    assert_eq!(as_user(user(3), my_delegate), None);
+}
+
+#[test]
+fn own_ballots_override_the_delegate() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    as_user(user(3), || delegate(user(2))).unwrap();
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    cast(1, user(3), VoteTypes::Approve).unwrap();
+    assert_eq!(get_delegated_tally(1), tally(1, 1, 0));
+    as_user(user(4), || delegate(user(2))).unwrap();
+    as_user(user(4), undelegate);
+    assert_eq!(get_delegated_tally(1), tally(1, 1, 0));
+}
+
+#[test]
+fn delegating_to_yourself_is_refused() {
+    setup();
+    assert_eq!(as_user(user(2), || delegate(user(2))), Err(VoteError::InvalidDelegation));
+    assert_eq!(as_user(user(2), my_delegate), None);
+}
+
+#[test]
+fn delegators_and_delegates_see_each_other() {
+    setup();
+    as_user(user(3), || delegate(user(2))).unwrap();
+    as_user(user(4), || delegate(user(2))).unwrap();
+    as_user(user(5), || delegate(user(6))).unwrap();
+    assert_eq!(as_user(user(3), my_delegate), Some(user(2)));
+    assert_eq!(as_user(user(2), my_delegators), vec![user(3), user(4)]);
+    as_user(user(4), || delegate(user(6))).unwrap();
+    assert_eq!(as_user(user(2), my_delegators), vec![user(3)]);
+    assert_eq!(as_user(user(6), my_delegators), vec![user(4), user(5)]);
+    assert_eq!(as_user(user(2), my_delegate), None);
+}
+
+#[test]
+fn expired_delegators_are_not_listed() {
+    setup();
+    let expiry = env::time() + 1_000;
+    as_user(user(3), || delegate_until(user(2), expiry)).unwrap();
+    assert_eq!(as_user(user(2), my_delegators), vec![user(3)]);
+    env::advance_time(1_000);
+    assert!(as_user(user(2), my_delegators).is_empty());
+}
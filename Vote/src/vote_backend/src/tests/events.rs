@@ -0,0 +1,89 @@
+use super::*;
+
+fn private_voters(key: u64) {
+    let hidden = CreateProposal {
+        private_voters: Some(true),
+        ..proposal("Rotate the signing keys")
+    };
+    as_user(user(1), || create_proposal(key, hidden)).unwrap();
+}
+
+fn ballot_callers(events: &[Event]) -> Vec<Principal> {
+    events
+        .iter()
+        .filter(|event| matches!(event.kind, EventKind::Voted { .. }))
+        .map(|event| event.caller)
+        .collect()
+}
+
+#[test]
+fn private_voters_are_redacted_from_events() {
+    setup();
+    private_voters(1);
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    let events = as_user(user(3), || get_proposal_events(1, 0, 10));
+    assert_eq!(ballot_callers(&events), vec![Principal::anonymous()]);
+    assert_eq!(events[0].caller, user(1));
+    let feed = as_user(user(3), || activity_feed(0, 10));
+    assert_eq!(ballot_callers(&feed), vec![Principal::anonymous()]);
+}
+
+#[test]
+fn private_voters_stay_redacted_after_the_proposal_is_gone() {
+    setup();
+    private_voters(1);
+    private_voters(2);
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(bulk_delete_proposals(vec![1]), vec![Ok(())]);
+    create(2, user(1), "Publish the roadmap");
+    for key in [1, 2] {
+        let events = as_user(user(3), || get_proposal_events(key, 0, 10));
+        assert_eq!(ballot_callers(&events), vec![Principal::anonymous()]);
+    }
+    let feed = as_user(user(3), || activity_feed(0, 10));
+    assert_eq!(ballot_callers(&feed), vec![Principal::anonymous(); 2]);
+    assert_eq!(ballot_callers(&activity_feed(0, 10)), vec![user(2); 2]);
+}
+
+#[test]
+fn public_voters_and_admins_see_who_voted() {
+    setup();
+    private_voters(1);
+    create(2, user(1), "Publish the roadmap");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(2, user(2), VoteTypes::Reject).unwrap();
+    assert_eq!(ballot_callers(&get_proposal_events(1, 0, 10)), vec![user(2)]);
+    let events = as_user(user(3), || get_proposal_events(2, 0, 10));
+    assert_eq!(ballot_callers(&events), vec![user(2)]);
+}
+
+#[test]
+fn replaying_the_events_rebuilds_the_counters() {
+    setup();
+    create(1, user(1), "Publish the roadmap");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Approve).unwrap();
+    as_user(user(1), || end_proposal(1)).unwrap();
+    let replayed = replay_events(1).ok().unwrap();
+    assert_eq!(replayed.tally(), Tally { approve: 2, reject: 0, pass: 0 });
+    assert_eq!(replayed.voted, vec![user(2), user(3)]);
+    assert!(!replayed.is_active);
+    assert_eq!(as_user(user(2), || replay_events(1)).err(), Some(VoteError::Unauthorized));
+    assert_eq!(replay_events(2).err(), Some(VoteError::NoProposal));
+}
+
+#[test]
+fn widest_event_fits_its_slot() {
+    let widest = Event {
+        proposal_key: u64::MAX,
+        caller: Principal::from_slice(&[0xff; 29]),
+        timestamp: u64::MAX,
+        kind: EventKind::Voted {
+            choice: VoteTypes::Approve,
+            weight: u64::MAX,
+        },
+        private_voters: Some(true),
+    };
+    assert!(widest.to_bytes().len() <= Event::MAX_SIZE as usize);
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+fn target() -> Principal {
+    Principal::from_slice(&[0xee; 10])
+}
+
+fn approved_with_call(key: u64) {
+    let payload = ExecutionPayload {
+        target: target(),
+        method: "apply".to_string(),
+        args: Encode!().unwrap(),
+    };
+    let call = CreateProposal {
+        execution: Some(payload),
+        ..proposal("Apply the new fee schedule")
+    };
+    as_user(user(1), || create_proposal(key, call)).unwrap();
+    for voter in 10..15 {
+        cast(key, user(voter), VoteTypes::Approve).unwrap();
+    }
+}
+
+#[test]
+fn approved_proposal_runs_only_after_voting_ends() {
+    setup();
+    env::on_call(target(), "apply", |_| Ok(Encode!().unwrap()));
+    approved_with_call(1);
+    assert_eq!(block_on(execute_proposal(1)), Err(VoteError::ProposalActive));
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(block_on(execute_proposal(1)), Ok(()));
+    assert_eq!(block_on(execute_proposal(1)), Err(VoteError::AlreadyExecuted));
+}
+
+#[test]
+fn failed_call_can_be_retried() {
+    setup();
+    approved_with_call(1);
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(block_on(execute_proposal(1)), Err(VoteError::ExecutionFailed));
+    assert!(!get_proposal(1).unwrap().executed);
+}
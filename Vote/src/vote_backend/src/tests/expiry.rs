@@ -0,0 +1,110 @@
+use super::*;
+
+const TTL: u64 = 1_000;
+
+// Proposal 1 from `user(1)`, created under a TTL and already past its deadline.
+fn expired_proposal() {
+    setup();
+    set_default_ttl(Some(TTL)).unwrap();
+    create(1, user(1), "Repaint the hall");
+    env::advance_time(TTL);
+}
+
+#[test]
+fn default_ttl_sets_the_deadline() {
+    setup();
+    set_default_ttl(Some(TTL)).unwrap();
+    create(1, user(1), "Repaint the hall");
+    assert_eq!(get_proposal(1).unwrap().deadline, Some(env::time() + TTL));
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(TTL);
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Err(VoteError::ProposalNotActive));
+}
+
+#[test]
+fn expired_proposals_are_not_open_before_the_sweep() {
+    expired_proposal();
+    assert_eq!(count_active_by_owner(user(1)), 0);
+    assert!(as_user(user(2), || my_eligible_proposals(0, 10)).is_empty());
+    assert!(get_undecided_proposals(0, 10).is_empty());
+}
+
+#[test]
+fn expired_proposals_do_not_hold_the_active_limit_or_their_description() {
+    expired_proposal();
+    set_max_active_proposals(Some(1)).unwrap();
+    set_reject_duplicate_descriptions(true).unwrap();
+    create(2, user(1), "Repaint the hall");
+}
+
+#[test]
+fn expired_votes_cannot_be_cancelled() {
+    setup();
+    set_default_ttl(Some(TTL)).unwrap();
+    create(1, user(1), "Repaint the hall");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(TTL);
+    let cancelled = as_user(user(2), || cancel_recent_vote(1));
+    assert_eq!(cancelled, Err(VoteError::ProposalNotActive));
+}
+
+#[test]
+fn sweep_ends_expired_proposals_and_notifies() {
+    expired_proposal();
+    set_subscriber(Some(user(9))).unwrap();
+    create(2, user(1), "Keep the hall");
+    assert_eq!(as_user(user(1), sweep_expired_proposals), Err(VoteError::Unauthorized));
+    assert_eq!(sweep_expired_proposals(), Ok(1));
+    assert!(!get_proposal(1).unwrap().is_active);
+    assert!(get_proposal(2).unwrap().is_active);
+    assert_eq!(ACTIVE_COUNT.with(|c| *c.borrow().get()), 1);
+    let ended = get_proposal_events(1, 0, 10).pop().unwrap();
+    assert!(matches!(ended.kind, EventKind::Ended));
+    assert_eq!(ended.caller, Principal::anonymous());
+    let notifications = env::take_notifications();
+    assert_eq!(notifications.len(), 1);
+    let (key, status): (u64, ProposalStatus) =
+        candid::utils::decode_args(&notifications[0].2).unwrap();
+    assert_eq!((notifications[0].0, key, status), (user(9), 1, ProposalStatus::Undecided));
+    assert_eq!(sweep_expired_proposals(), Ok(0));
+}
+
+#[test]
+fn sweep_follows_edited_deadlines_and_activation() {
+    setup();
+    let draft = CreateProposal {
+        is_active: false,
+        deadline: Some(env::time() + TTL),
+        ..proposal("Paint the hall")
+    };
+    assert_eq!(try_create(1, user(1), draft), Ok(()));
+    let timed = CreateProposal {
+        deadline: Some(env::time() + TTL),
+        ..proposal("Repaint the hall")
+    };
+    assert_eq!(try_create(2, user(1), timed), Ok(()));
+    let later = CreateProposal {
+        deadline: Some(env::time() + TTL * 2),
+        ..proposal("Repaint the hall")
+    };
+    as_user(user(1), || edit_proposal(2, later)).unwrap();
+    env::advance_time(TTL);
+    assert_eq!(DEADLINES.with(|d| d.borrow().len()), 1);
+    assert_eq!(sweep_expired_proposals(), Ok(0));
+    // A draft activated past its deadline is closed by the next sweep.
+    as_user(user(1), || activate_proposal(1)).unwrap();
+    assert_eq!(sweep_expired_proposals(), Ok(1));
+    env::advance_time(TTL);
+    assert_eq!(sweep_expired_proposals(), Ok(1));
+    assert!(DEADLINES.with(|d| d.borrow().is_empty()));
+}
+
+#[test]
+fn deadline_index_is_rebuilt_after_an_upgrade() {
+    expired_proposal();
+    create(2, user(1), "Keep the hall");
+    DEADLINES.with(|d| d.borrow_mut().clear());
+    post_upgrade();
+    assert_eq!(DEADLINES.with(|d| d.borrow().len()), 2);
+    assert_eq!(sweep_expired_proposals(), Ok(1));
+}
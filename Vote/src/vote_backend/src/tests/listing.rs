@@ -0,0 +1,196 @@
+use super::*;
+
+fn keys(proposals: Vec<(u64, Proposal)>) -> Vec<u64> {
+    proposals.into_iter().map(|(key, _)| key).collect()
+}
+
+#[test]
+fn key_range_is_half_open() {
+    setup();
+    for key in 1..100 {
+        create(key, user(1), &format!("Shard proposal {key}"));
+    }
+    assert_eq!(keys(get_proposals_in_range(20, 40, 100)), (20..40).collect::<Vec<_>>());
+    assert_eq!(keys(get_proposals_in_range(20, 40, 5)), (20..25).collect::<Vec<_>>());
+    assert!(get_proposals_in_range(40, 20, 100).is_empty());
+}
+
+#[test]
+fn active_proposals_are_counted_per_owner() {
+    setup();
+    create(1, user(1), "Open the garden");
+    create(2, user(1), "Close the garden");
+    create(3, user(1), "Water the garden");
+    create(4, user(2), "Fence the garden");
+    as_user(user(1), || end_proposal(3)).unwrap();
+    assert_eq!(count_active_by_owner(user(1)), 2);
+    assert_eq!(count_active_by_owner(user(2)), 1);
+    assert_eq!(count_active_by_owner(user(3)), 0);
+}
+
+#[test]
+fn scans_are_refused_below_the_cycle_floor() {
+    setup();
+    create(1, user(1), "Shard proposal one");
+    set_min_scan_balance(Some(1_000)).unwrap();
+    env::set_balance(999);
+    assert_eq!(search("shard".to_string(), 0, 10).err(), Some(VoteError::InsufficientCycles));
+    assert_eq!(export_all(0, 10).err(), Some(VoteError::InsufficientCycles));
+    env::set_balance(1_000);
+    assert_eq!(keys(search("shard".to_string(), 0, 10).unwrap()), vec![1]);
+    assert_eq!(keys(export_all(0, 10).unwrap()), vec![1]);
+}
+
+fn filter(owner: Option<Principal>, tag: Option<&str>, min_votes: Option<u32>) -> ProposalFilter {
+    ProposalFilter {
+        owner,
+        active_only: true,
+        tag: tag.map(str::to_string),
+        min_votes,
+    }
+}
+
+fn tagged(key: u64, owner: Principal, tags: &[&str]) {
+    let settings = CreateProposal {
+        tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+        ..proposal(&format!("Tagged proposal {key}"))
+    };
+    assert_eq!(try_create(key, owner, settings), Ok(()));
+}
+
+#[test]
+fn filters_combine_every_predicate() {
+    setup();
+    tagged(1, user(1), &["Parks"]);
+    tagged(2, user(1), &["parks", "budget"]);
+    tagged(3, user(1), &["budget"]);
+    tagged(4, user(2), &["parks"]);
+    tagged(5, user(1), &["parks"]);
+    for key in [1, 3, 4, 5] {
+        cast(key, user(10), VoteTypes::Approve).unwrap();
+        cast(key, user(11), VoteTypes::Reject).unwrap();
+    }
+    as_user(user(1), || end_proposal(5)).unwrap();
+    let owned = filter(Some(user(1)), None, Some(2));
+    assert_eq!(keys(list_proposals_filtered(owned, 0, 10).unwrap()), vec![1, 3]);
+    let parks = filter(Some(user(1)), Some("PARKS"), Some(2));
+    assert_eq!(keys(list_proposals_filtered(parks, 0, 10).unwrap()), vec![1]);
+    let any_votes = filter(None, Some("parks"), None);
+    assert_eq!(keys(list_proposals_filtered(any_votes, 1, 10).unwrap()), vec![2, 4]);
+    assert_eq!(count_matching(filter(None, Some("parks"), None)), Ok(3));
+}
+
+#[test]
+fn tags_are_bounded() {
+    assert_eq!(validate_tags(&None), Ok(()));
+    let most = Some(vec!["t".repeat(MAX_TAG_LEN); MAX_TAGS]);
+    assert_eq!(validate_tags(&most), Ok(()));
+    let too_many = Some(vec!["t".to_string(); MAX_TAGS + 1]);
+    assert_eq!(validate_tags(&too_many), Err(VoteError::InvalidTag));
+    let too_long = Some(vec!["t".repeat(MAX_TAG_LEN + 1)]);
+    assert_eq!(validate_tags(&too_long), Err(VoteError::InvalidTag));
+    assert_eq!(validate_tags(&Some(vec![" ".to_string()])), Err(VoteError::InvalidTag));
+}
+
+#[test]
+fn eligible_proposals_are_the_actionable_ones() {
+    setup();
+    for key in 1..=5 {
+        create(key, user(1), &format!("Ballot {key}"));
+    }
+    // Whitelisted with the caller, whitelisted without, voted, closed, open.
+    as_user(user(1), || set_eligible_voters(1, vec![user(2)])).unwrap();
+    as_user(user(1), || set_eligible_voters(2, vec![user(3)])).unwrap();
+    cast(3, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(1), || end_proposal(4)).unwrap();
+    assert_eq!(keys(as_user(user(2), || my_eligible_proposals(0, 10))), vec![1, 5]);
+    assert_eq!(keys(as_user(user(2), || my_eligible_proposals(1, 10))), vec![5]);
+    assert_eq!(keys(as_user(user(3), || my_eligible_proposals(0, 10))), vec![2, 3, 5]);
+    set_require_registration(true).unwrap();
+    assert!(as_user(user(2), || my_eligible_proposals(0, 10)).is_empty());
+}
+
+#[test]
+fn listed_status_matches_the_standalone_one() {
+    setup();
+    for key in 1..=4 {
+        create(key, user(1), &format!("Ballot {key}"));
+    }
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+        cast(3, user(voter), VoteTypes::Reject).unwrap();
+    }
+    as_user(user(1), || end_proposal(3)).unwrap();
+    let listed = list_with_status(0, 10);
+    assert_eq!(listed.len(), 4);
+    for (key, proposal, status) in listed {
+        assert_eq!(Some(status.as_str()), get_proposal_status(key));
+        assert_eq!(proposal.description, format!("Ballot {key}"));
+    }
+    let page: Vec<_> = list_with_status(2, 1).into_iter().map(|(k, _, s)| (k, s)).collect();
+    assert_eq!(page, vec![(3, ProposalStatus::Rejected)]);
+}
+
+#[test]
+fn matching_counts_agree_with_the_filtered_listing() {
+    setup();
+    for key in 1..=6 {
+        let owner = user(1 + key as u8 % 2);
+        tagged(key, owner, if key < 4 { &["parks"] } else { &["budget"] });
+        for voter in 0..key as u8 {
+            cast(key, user(10 + voter), VoteTypes::Approve).unwrap();
+        }
+    }
+    as_user(user(2), || end_proposal(1)).unwrap();
+    let filters = [
+        (None, None, None),
+        (Some(user(2)), None, None),
+        (None, Some("parks"), Some(2)),
+        (Some(user(1)), Some("budget"), Some(5)),
+        (Some(user(3)), None, None),
+    ];
+    for (owner, tag, min_votes) in filters {
+        let listed = list_proposals_filtered(filter(owner, tag, min_votes), 0, 100).unwrap();
+        assert_eq!(count_matching(filter(owner, tag, min_votes)), Ok(listed.len() as u64));
+    }
+    assert_eq!(count_matching(filter(None, None, None)), Ok(5));
+}
+
+fn try_tagged(key: u64, tags: &[&str]) -> Result<(), VoteError> {
+    let settings = CreateProposal {
+        tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+        ..proposal(&format!("Tagged proposal {key}"))
+    };
+    try_create(key, user(1), settings)
+}
+
+#[test]
+fn distinct_tags_are_capped_but_reusable() {
+    setup();
+    set_max_distinct_tags(Some(2)).unwrap();
+    assert_eq!(try_tagged(1, &["parks", "budget"]), Ok(()));
+    assert_eq!(try_tagged(2, &["ops"]), Err(VoteError::TagLimitReached));
+    assert_eq!(try_tagged(2, &["PARKS"]), Ok(()));
+    assert_eq!(try_tagged(3, &["budget", "ops"]), Err(VoteError::TagLimitReached));
+    assert_eq!(bulk_delete_proposals(vec![1]), vec![Ok(())]);
+    // "parks" is still used by proposal 2, "budget" is gone.
+    assert_eq!(try_tagged(3, &["ops"]), Ok(()));
+    assert_eq!(try_tagged(4, &["budget"]), Err(VoteError::TagLimitReached));
+}
+
+fn cloud(expected: &[(&str, u64)]) -> Vec<(String, u64)> {
+    expected.iter().map(|(tag, count)| (tag.to_string(), *count)).collect()
+}
+
+#[test]
+fn tags_are_listed_by_use() {
+    setup();
+    assert_eq!(try_tagged(1, &["Parks", "budget"]), Ok(()));
+    assert_eq!(try_tagged(2, &["parks", "ops"]), Ok(()));
+    assert_eq!(try_tagged(3, &["parks", "budget", "art"]), Ok(()));
+    let all = cloud(&[("parks", 3), ("budget", 2), ("art", 1), ("ops", 1)]);
+    assert_eq!(list_tags(0, 10), all);
+    assert_eq!(list_tags(1, 2), cloud(&[("budget", 2), ("art", 1)]));
+    assert_eq!(bulk_delete_proposals(vec![3]), vec![Ok(())]);
+    assert_eq!(list_tags(0, 10), cloud(&[("parks", 2), ("budget", 1), ("ops", 1)]));
+}
@@ -0,0 +1,87 @@
+// Endpoints are called directly against the stand-in replica in `env`. libtest
+// runs every test on a fresh thread, and all canister state (stable memory
+// included) is thread-local, so each test starts from an empty canister.
+use super::*;
+use candid::Principal;
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+
+mod archive;
+mod certification;
+mod comments;
+mod config;
+mod delay;
+mod delegation;
+mod events;
+mod execution;
+mod expiry;
+mod listing;
+mod outcomes;
+mod private;
+mod proposals;
+mod reservations;
+mod reveal;
+mod revisions;
+mod storage;
+mod timeline;
+mod upgrade;
+mod voters;
+mod voting;
+mod weights;
+
+pub(crate) fn admin() -> Principal {
+    Principal::from_slice(&[0xad; 10])
+}
+
+pub(crate) fn user(n: u8) -> Principal {
+    Principal::from_slice(&[n; 10])
+}
+
+// Installs the canister with `admin()` as its controller and leaves `admin()` as the caller.
+pub(crate) fn setup() {
+    env::set_caller(admin());
+    init();
+}
+
+pub(crate) fn as_user<R>(who: Principal, f: impl FnOnce() -> R) -> R {
+    let previous = env::caller();
+    env::set_caller(who);
+    let result = f();
+    env::set_caller(previous);
+    result
+}
+
+// The fake replica answers inter-canister calls synchronously, so one poll is enough.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future is waiting on something the fake replica never answers"),
+    }
+}
+
+pub(crate) fn proposal(description: &str) -> CreateProposal {
+    CreateProposal {
+        description: description.to_string(),
+        is_active: true,
+        ..CreateProposal::default()
+    }
+}
+
+// `create_proposal` without the overwritten proposal, which has no Debug.
+pub(crate) fn try_create(
+    key: u64,
+    owner: Principal,
+    settings: CreateProposal,
+) -> Result<(), VoteError> {
+    as_user(owner, || create_proposal(key, settings)).map(|_| ())
+}
+
+pub(crate) fn create(key: u64, owner: Principal, description: &str) {
+    as_user(owner, || create_proposal(key, proposal(description)))
+        .unwrap_or_else(|err| panic!("creating proposal {key} failed: {err:?}"));
+}
+
+pub(crate) fn cast(key: u64, who: Principal, choice: VoteTypes) -> Result<(), VoteError> {
+    as_user(who, || block_on(vote(key, choice, None, None)))
+}
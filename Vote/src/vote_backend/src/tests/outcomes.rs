@@ -0,0 +1,600 @@
+use super::*;
+
+fn cached(key: u64) -> Option<ProposalStatus> {
+    STATUS_CACHE.with(|c| c.borrow().get(&key).copied())
+}
+
+#[test]
+fn status_cache_is_refreshed_by_votes() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    assert_eq!(cached(1), Some(ProposalStatus::Undecided));
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(cached(1), Some(ProposalStatus::Approved));
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    for voter in 20..26 {
+        cast(1, user(voter), VoteTypes::Reject).unwrap();
+    }
+    assert_eq!(cached(1), Some(ProposalStatus::Rejected));
+    assert_eq!(get_proposal_status(1), Some("Rejected"));
+}
+
+#[test]
+fn repeated_status_reads_return_the_cached_verdict() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    let first = get_proposal_status(1);
+    assert_eq!(first, Some("Approved"));
+    assert_eq!(get_proposal_status(1), first);
+    assert_eq!(cached(1), Some(ProposalStatus::Approved));
+}
+
+#[test]
+fn status_cache_is_rebuilt_after_an_upgrade() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    STATUS_CACHE.with(|c| c.borrow_mut().clear());
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+fn finalizations() -> Vec<(Principal, u64, ProposalStatus)> {
+    env::take_notifications()
+        .into_iter()
+        .map(|(to, method, args)| {
+            assert_eq!(method, "proposal_finalized");
+            let (key, status) = candid::utils::decode_args(&args).unwrap();
+            (to, key, status)
+        })
+        .collect()
+}
+
+#[test]
+fn ending_a_proposal_notifies_the_subscriber() {
+    setup();
+    let subscriber = user(99);
+    set_subscriber(Some(subscriber)).unwrap();
+    create(1, user(1), "Raise the cap");
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(finalizations(), vec![(subscriber, 1, ProposalStatus::Approved)]);
+}
+
+#[test]
+fn ending_without_a_subscriber_sends_nothing() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert!(finalizations().is_empty());
+}
+
+#[test]
+fn ending_a_missing_proposal_is_an_error() {
+    setup();
+    assert_eq!(end_proposal(7), Err(VoteError::NoProposal));
+}
+
+#[test]
+fn is_decided_needs_a_quorate_outcome() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    assert!(!is_decided(1));
+    for voter in 11..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert!(is_decided(1));
+    assert!(!is_decided(2));
+}
+
+#[test]
+fn percentage_quorum_follows_the_eligible_set() {
+    setup();
+    let settings = CreateProposal {
+        quorum_pct: Some(50),
+        ..proposal("Raise the cap")
+    };
+    as_user(user(1), || create_proposal(1, settings)).unwrap();
+    let eligible: Vec<_> = (10..14).map(user).collect();
+    as_user(user(1), || set_eligible_voters(1, eligible)).unwrap();
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    cast(1, user(11), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    for voter in 14..18 {
+        as_user(user(1), || add_eligible_voter(1, user(voter))).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    let removed = as_user(user(2), || remove_eligible_voter(1, user(14)));
+    assert_eq!(removed, Err(VoteError::Unauthorized));
+}
+
+#[test]
+fn quorum_percentages_above_100_are_refused() {
+    assert_eq!(validate_quorum(Some(100)), Ok(()));
+    assert_eq!(validate_quorum(Some(101)), Err(VoteError::InvalidQuorum));
+}
+
+// The owner approves and one other voter rejects.
+fn owner_against_one(owner_vote_counts: bool) -> Option<&'static str> {
+    setup();
+    let settings = CreateProposal {
+        owner_vote_counts: Some(owner_vote_counts),
+        ..proposal("Raise the cap")
+    };
+    try_create(1, user(1), settings).unwrap();
+    as_user(user(1), || set_quorum(1, 1)).unwrap();
+    cast(1, user(1), VoteTypes::Approve).unwrap();
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    assert_eq!(get_proposal(1).unwrap().approve, 1);
+    get_proposal_status(1)
+}
+
+#[test]
+fn owner_votes_count_by_default() {
+    assert_eq!(owner_against_one(true), Some("Approved"));
+}
+
+#[test]
+fn owner_votes_can_be_left_out_of_the_outcome() {
+    assert_eq!(owner_against_one(false), Some("Rejected"));
+}
+
+fn metrics(key: u64) -> (f64, f64, f64, u32, bool) {
+    let m = get_proposal_metrics(key).unwrap();
+    (m.approval_pct, m.rejection_pct, m.pass_pct, m.turnout, m.quorum_met)
+}
+
+#[test]
+fn metrics_describe_the_distribution() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    assert_eq!(metrics(1), (0.0, 0.0, 0.0, 0, false));
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    cast(1, user(11), VoteTypes::Approve).unwrap();
+    cast(1, user(12), VoteTypes::Reject).unwrap();
+    cast(1, user(13), VoteTypes::Pass).unwrap();
+    assert_eq!(metrics(1), (50.0, 25.0, 25.0, 4, false));
+    cast(1, user(14), VoteTypes::Approve).unwrap();
+    assert_eq!(metrics(1), (60.0, 20.0, 20.0, 5, true));
+    assert!(get_proposal_metrics(2).is_none());
+}
+
+#[test]
+fn undecided_proposals_list_the_votes_missing_for_quorum() {
+    setup();
+    for key in 1..=4 {
+        create(key, user(1), &format!("Option {key}"));
+    }
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    cast(1, user(11), VoteTypes::Reject).unwrap();
+    for voter in 10..15 {
+        cast(2, user(voter), VoteTypes::Approve).unwrap();
+    }
+    // Quorum without a majority is still undecided.
+    let choices = [VoteTypes::Approve, VoteTypes::Reject, VoteTypes::Pass];
+    for (voter, choice) in (10..16).zip(choices.into_iter().cycle()) {
+        cast(4, user(voter), choice).unwrap();
+    }
+    assert_eq!(get_undecided_proposals(0, 10), vec![(1, 3), (3, 5), (4, 0)]);
+    assert_eq!(get_undecided_proposals(1, 1), vec![(3, 5)]);
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(get_undecided_proposals(0, 10), vec![(3, 5), (4, 0)]);
+}
+
+#[test]
+fn recount_restores_drifted_counters() {
+    setup();
+    create(1, user(1), "Adopt the charter");
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    cast(1, user(11), VoteTypes::Approve).unwrap();
+    cast(1, user(12), VoteTypes::Pass).unwrap();
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = p.borrow().get(&1).unwrap();
+        proposal.set_tally(Tally {
+            approve: 7,
+            reject: 3,
+            pass: 0,
+        });
+        p.borrow_mut().insert(1, proposal);
+    });
+    assert_eq!(as_user(user(1), || recount(1)), Err(VoteError::Unauthorized));
+    let fixed = Tally {
+        approve: 2,
+        reject: 0,
+        pass: 1,
+    };
+    assert_eq!(recount(1), Ok(fixed));
+    assert_eq!(get_tally(1), Some(fixed));
+    assert_eq!(recount(2), Err(VoteError::NoProposal));
+}
+
+// Proposal 1 with three approvals and three rejections.
+fn even_split() {
+    create(1, user(1), "Split the fund");
+    for voter in 10..16 {
+        let choice = if voter < 13 { VoteTypes::Approve } else { VoteTypes::Reject };
+        cast(1, user(voter), choice).unwrap();
+    }
+}
+
+#[test]
+fn even_split_approves_under_a_lenient_majority() {
+    setup();
+    even_split();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+#[test]
+fn even_split_is_undecided_under_a_strict_majority() {
+    setup();
+    even_split();
+    assert_eq!(set_strict_majority(true), Ok(()));
+    assert_eq!(cached(1), None);
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    cast(1, user(16), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+#[test]
+fn batch_status_answers_positionally() {
+    setup();
+    create(1, user(1), "Adopt the charter");
+    create(2, user(1), "Amend the charter");
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+        cast(2, user(voter), VoteTypes::Reject).unwrap();
+    }
+    create(3, user(1), "Drop the charter");
+    let statuses = batch_status(vec![2, 9, 1, 3, 2]);
+    assert_eq!(
+        statuses,
+        vec![
+            Some(ProposalStatus::Rejected),
+            None,
+            Some(ProposalStatus::Approved),
+            Some(ProposalStatus::Undecided),
+            Some(ProposalStatus::Rejected),
+        ]
+    );
+}
+
+#[test]
+#[should_panic(expected = "too many keys")]
+fn batch_status_bounds_its_input() {
+    setup();
+    batch_status((0..=MAX_BATCH_SIZE as u64).collect());
+}
+
+#[test]
+fn participation_is_averaged_over_proposals() {
+    setup();
+    assert_eq!(overall_participation(), 0.0);
+    for key in 1..=3 {
+        create(key, user(1), &format!("Option {key}"));
+    }
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    for voter in 10..14 {
+        cast(2, user(voter), VoteTypes::Reject).unwrap();
+    }
+    assert_eq!(overall_participation(), 5.0 / 3.0);
+}
+
+#[test]
+fn statuses_are_counted_across_open_and_closed_proposals() {
+    setup();
+    let outcomes = [(1, VoteTypes::Approve), (2, VoteTypes::Reject), (3, VoteTypes::Pass)];
+    for (key, choice) in outcomes {
+        create(key, user(1), &format!("Option {key}"));
+        for voter in 10..15 {
+            cast(key, user(voter), choice).unwrap();
+        }
+    }
+    create(4, user(1), "Option 4");
+    create(5, user(1), "Option 5");
+    as_user(user(1), || end_proposal(1)).unwrap();
+    let counts = count_by_status();
+    let counted = (counts.undecided, counts.approved, counts.rejected, counts.passed);
+    assert_eq!(counted, (2, 1, 1, 1));
+}
+
+#[test]
+fn default_quorum_percentage_applies_to_new_proposals() {
+    setup();
+    create(1, user(1), "Keep the cap");
+    assert_eq!(set_default_quorum_pct(Some(101)), Err(VoteError::InvalidQuorum));
+    assert_eq!(set_default_quorum_pct(Some(40)), Ok(()));
+    create(2, user(1), "Raise the cap");
+    let eligible: Vec<_> = (10..20).map(user).collect();
+    for key in [1, 2] {
+        as_user(user(1), || set_eligible_voters(key, eligible.clone())).unwrap();
+    }
+    for voter in 10..13 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+        cast(2, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(get_proposal_status(2), Some("Undecided"));
+    cast(2, user(13), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal_status(2), Some("Approved"));
+    cast(1, user(13), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+}
+
+#[test]
+fn percentage_quorum_without_eligibility_falls_back_to_the_absolute_one() {
+    setup();
+    set_default_quorum_pct(Some(40)).unwrap();
+    create(1, user(1), "Raise the cap");
+    for voter in 10..14 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    cast(1, user(14), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+#[test]
+fn earliest_of_tied_prefers_the_option_that_got_there_first() {
+    let mut proposal = new_proposal(proposal("Split the fund"), user(1));
+    proposal.reached_at = OptionTimes {
+        approve: 30,
+        reject: 20,
+        pass: 10,
+    };
+    let tally = |approve, reject, pass| Tally {
+        approve,
+        reject,
+        pass,
+    };
+    assert_eq!(earliest_of_tied(&tally(3, 3, 1), &proposal), Some(ProposalStatus::Rejected));
+    assert_eq!(earliest_of_tied(&tally(3, 3, 3), &proposal), Some(ProposalStatus::Passed));
+    assert_eq!(earliest_of_tied(&tally(4, 3, 3), &proposal), None);
+    assert_eq!(earliest_of_tied(&tally(0, 0, 0), &proposal), None);
+    proposal.binary = true;
+    assert_eq!(earliest_of_tied(&tally(3, 3, 3), &proposal), Some(ProposalStatus::Rejected));
+}
+
+#[test]
+fn earliest_majority_breaks_a_tie_by_time() {
+    setup();
+    create(1, user(1), "Split the fund");
+    for voter in 10..16 {
+        env::advance_time(10);
+        let choice = if voter < 13 { VoteTypes::Reject } else { VoteTypes::Approve };
+        cast(1, user(voter), choice).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(set_tie_break(TieBreak::EarliestMajority), Ok(()));
+    assert_eq!(get_proposal_status(1), Some("Rejected"));
+}
+
+#[test]
+fn approval_needs_the_minimum_count_as_well() {
+    setup();
+    // Three of six eligible voters make the quorum.
+    let settings = CreateProposal {
+        quorum_pct: Some(50),
+        ..proposal("Raise the cap")
+    };
+    assert_eq!(try_create(1, user(1), settings), Ok(()));
+    let eligible: Vec<_> = (10..16).map(user).collect();
+    as_user(user(1), || set_eligible_voters(1, eligible)).unwrap();
+    for voter in 10..13 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(set_min_approve_count(Some(5)), Ok(()));
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    cast(1, user(13), VoteTypes::Approve).unwrap();
+    cast(1, user(14), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+#[test]
+fn quorum_and_threshold_changes_re_evaluate_the_status() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    cast(1, user(11), VoteTypes::Approve).unwrap();
+    cast(1, user(12), VoteTypes::Reject).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    let owner = |f: fn() -> Result<(), VoteError>| as_user(user(1), f);
+    assert_eq!(owner(|| set_quorum(1, 3)), Ok(()));
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(owner(|| set_threshold(1, 70)), Ok(()));
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    assert_eq!(owner(|| set_threshold(1, 101)), Err(VoteError::InvalidThreshold));
+    assert_eq!(owner(|| set_threshold(1, 0)), Err(VoteError::InvalidThreshold));
+    assert_eq!(as_user(user(2), || set_quorum(1, 1)), Err(VoteError::Unauthorized));
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(owner(|| set_threshold(1, 60)), Err(VoteError::ProposalNotActive));
+}
+
+#[test]
+fn auto_finalize_closes_on_the_passing_vote() {
+    setup();
+    set_auto_finalize(true).unwrap();
+    set_subscriber(Some(user(9))).unwrap();
+    create(1, user(1), "Raise the cap");
+    cast(1, user(10), VoteTypes::Reject).unwrap();
+    for voter in 11..14 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert!(get_proposal(1).unwrap().is_active);
+    cast(1, user(14), VoteTypes::Approve).unwrap();
+    assert!(!get_proposal(1).unwrap().is_active);
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(finalizations(), vec![(user(9), 1, ProposalStatus::Approved)]);
+    assert_eq!(cast(1, user(15), VoteTypes::Reject), Err(VoteError::ProposalNotActive));
+    assert_eq!(ACTIVE_COUNT.with(|c| *c.borrow().get()), 0);
+}
+
+#[test]
+fn rejections_are_not_finalized_early() {
+    setup();
+    set_auto_finalize(true).unwrap();
+    create(1, user(1), "Raise the cap");
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Reject).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Rejected"));
+    assert!(get_proposal(1).unwrap().is_active);
+}
+
+#[test]
+fn winners_need_the_vote_floor() {
+    setup();
+    create(1, user(1), "Raise the cap");
+    as_user(user(1), || set_quorum(1, 3)).unwrap();
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    cast(1, user(11), VoteTypes::Approve).unwrap();
+    cast(1, user(12), VoteTypes::Pass).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(as_user(user(1), || set_min_winning_votes(1, Some(5))), Ok(()));
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    for voter in 13..16 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+fn announce(key: u64, choices: &[VoteTypes]) -> Option<String> {
+    for (voter, choice) in (10..).zip(choices) {
+        cast(key, user(voter), *choice).unwrap();
+    }
+    get_result_announcement(key)
+}
+
+#[test]
+fn announcements_name_the_outcome_and_its_share() {
+    setup();
+    use VoteTypes::{Approve, Pass, Reject};
+    create(1, user(1), "Raise cap");
+    let ballots = [Approve, Approve, Approve, Reject, Approve, Reject, Approve, Pass];
+    let approved = announce(1, &ballots);
+    assert_eq!(approved.unwrap(), "Proposal 1 'Raise cap' APPROVED with 62% (5/8)");
+    create(2, user(1), "Lower cap");
+    let rejected = announce(2, &[Reject, Reject, Reject, Approve, Pass]);
+    assert_eq!(rejected.unwrap(), "Proposal 2 'Lower cap' REJECTED with 60% (3/5)");
+    create(3, user(1), "Keep cap");
+    let undecided = announce(3, &[Approve, Reject]);
+    assert_eq!(undecided.unwrap(), "Proposal 3 'Keep cap' is UNDECIDED after 2 votes");
+    assert_eq!(get_result_announcement(4), None);
+}
+
+#[test]
+fn early_lock_refuses_votes_once_the_outcome_is_secured() {
+    setup();
+    set_early_lock(true).unwrap();
+    create(1, user(1), "Raise the cap");
+    create(2, user(1), "Lower the cap");
+    let eligible: Vec<_> = (10..17).map(user).collect();
+    as_user(user(1), || set_eligible_voters(1, eligible)).unwrap();
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+        cast(2, user(voter), VoteTypes::Approve).unwrap();
+    }
+    // Two remaining rejections could not overturn five approvals.
+    assert_eq!(cast(1, user(15), VoteTypes::Reject), Err(VoteError::ResultLocked));
+    assert_eq!(get_proposal(1).unwrap().reject, 0);
+    // Without an eligible set the remaining votes are unbounded.
+    assert_eq!(cast(2, user(15), VoteTypes::Reject), Ok(()));
+}
+
+#[test]
+fn outcomes_that_may_still_flip_stay_open() {
+    setup();
+    set_early_lock(true).unwrap();
+    create(1, user(1), "Raise the cap");
+    let eligible: Vec<_> = (10..20).map(user).collect();
+    as_user(user(1), || set_eligible_voters(1, eligible)).unwrap();
+    for voter in 10..14 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    cast(1, user(14), VoteTypes::Pass).unwrap();
+    // Five remaining rejections would outnumber the four approvals.
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(cast(1, user(15), VoteTypes::Reject), Ok(()));
+}
+
+#[test]
+fn tied_proposals_are_listed_until_decided() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    create(2, user(1), "Adopt the budget");
+    create(3, user(1), "Adopt the logo");
+    create(4, user(1), "Adopt the charter");
+    for (voter, choice) in [(2, VoteTypes::Approve), (3, VoteTypes::Reject)] {
+        cast(1, user(voter), choice).unwrap();
+        cast(4, user(voter), choice).unwrap();
+    }
+    for voter in 2..5 {
+        cast(2, user(voter), VoteTypes::Approve).unwrap();
+    }
+    // Closed short of quorum, so the tie is left undecided.
+    as_user(user(1), || end_proposal(4)).unwrap();
+    assert_eq!(get_proposal_status(4), Some("Undecided"));
+    assert_eq!(get_tied_proposals(0, 10), vec![1, 4]);
+    assert_eq!(get_tied_proposals(1, 10), vec![4]);
+}
+
+#[test]
+fn decided_proposals_come_with_their_outcome() {
+    setup();
+    let choices = [VoteTypes::Approve, VoteTypes::Reject, VoteTypes::Pass];
+    for (key, choice) in (1..).zip(choices) {
+        create(key, user(1), "Adopt the roadmap");
+        for voter in 2..7 {
+            cast(key, user(voter), choice).unwrap();
+        }
+    }
+    create(4, user(1), "Adopt the budget");
+    cast(4, user(2), VoteTypes::Approve).unwrap();
+    let decided = vec![
+        (1, ProposalStatus::Approved),
+        (2, ProposalStatus::Rejected),
+        (3, ProposalStatus::Passed),
+    ];
+    assert_eq!(get_decided_proposals(0, 10), decided);
+    assert_eq!(get_decided_proposals(2, 10), vec![(3, ProposalStatus::Passed)]);
+}
+
+#[test]
+fn pass_votes_only_make_quorum_when_they_count_as_turnout() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    for voter in 2..7 {
+        let choice = if voter < 5 { VoteTypes::Approve } else { VoteTypes::Pass };
+        cast(1, user(voter), choice).unwrap();
+    }
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+    assert_eq!(set_pass_is_turnout(false), Ok(()));
+    assert_eq!(get_proposal_status(1), Some("Undecided"));
+    cast(1, user(7), VoteTypes::Approve).unwrap();
+    cast(1, user(8), VoteTypes::Reject).unwrap();
+    assert_eq!(get_proposal_status(1), Some("Approved"));
+}
+
+#[test]
+fn quorum_progress_counts_up_past_quorum() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    assert_eq!(quorum_progress(1), Some((0, QUORUM)));
+    for voter in 2..6 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(quorum_progress(1), Some((4, QUORUM)));
+    cast(1, user(6), VoteTypes::Pass).unwrap();
+    assert_eq!(quorum_progress(1), Some((5, QUORUM)));
+    cast(1, user(7), VoteTypes::Reject).unwrap();
+    assert_eq!(quorum_progress(1), Some((6, QUORUM)));
+    assert_eq!(quorum_progress(2), None);
+}
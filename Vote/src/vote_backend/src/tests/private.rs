@@ -0,0 +1,126 @@
+use super::*;
+
+fn neuron_source() -> Principal {
+    Principal::from_slice(&[0xcc; 10])
+}
+
+fn private_tally(key: u64, settings: CreateProposal) {
+    let settings = CreateProposal {
+        private_tally: Some(true),
+        ..settings
+    };
+    as_user(user(1), || create_proposal(key, settings)).unwrap();
+}
+
+fn cast_private(key: u64, who: Principal, secret: Option<&str>) -> Result<(), VoteError> {
+    let secret = secret.map(str::to_string);
+    as_user(who, || block_on(vote_private(key, VoteTypes::Approve, secret)))
+}
+
+#[test]
+fn private_ballot_needs_the_secret() {
+    setup();
+    let gated = CreateProposal {
+        secret: Some("s3".to_string()),
+        ..proposal("Hire an auditor")
+    };
+    private_tally(1, gated);
+    assert_eq!(cast_private(1, user(2), None), Err(VoteError::Unauthorized));
+    assert_eq!(cast_private(1, user(2), Some("s3")), Ok(()));
+}
+
+#[test]
+fn private_ballot_follows_voter_policies() {
+    setup();
+    private_tally(1, proposal("Hire an auditor"));
+    set_require_registration(true).unwrap();
+    assert_eq!(cast_private(1, user(2), None), Err(VoteError::NotRegistered));
+    set_require_registration(false).unwrap();
+    set_min_account_age(1_000).unwrap();
+    assert_eq!(cast_private(1, user(2), None), Err(VoteError::AccountTooNew));
+}
+
+#[test]
+fn private_ballot_uses_fresh_neuron_power() {
+    setup();
+    set_neuron_source(Some(neuron_source())).unwrap();
+    env::on_call(neuron_source(), "get_voting_power", |_| Ok(Encode!(&7u64).unwrap()));
+    let weighted = CreateProposal {
+        weighted: Some(true),
+        ..proposal("Hire an auditor")
+    };
+    private_tally(1, weighted);
+    cast_private(1, user(2), None).unwrap();
+    assert_eq!(get_proposal(1).unwrap().approve, 7);
+    assert_eq!(NEURON_POWER.with(|n| n.borrow().len()), 0);
+}
+
+#[test]
+fn private_ballot_can_finalize_without_naming_the_voter() {
+    setup();
+    set_auto_finalize(true).unwrap();
+    private_tally(1, proposal("Hire an auditor"));
+    for voter in 10..15 {
+        cast_private(1, user(voter), None).unwrap();
+    }
+    assert!(!get_proposal(1).unwrap().is_active);
+    let ended = get_proposal_events(1, 0, 10).pop().unwrap();
+    assert!(matches!(ended.kind, EventKind::Ended));
+    assert_eq!(ended.caller, Principal::anonymous());
+}
+
+#[test]
+fn neuron_power_beyond_u32_counts_in_full() {
+    setup();
+    set_neuron_source(Some(neuron_source())).unwrap();
+    let power = u64::from(u32::MAX) * 3;
+    env::on_call(neuron_source(), "get_voting_power", move |_| Ok(Encode!(&power).unwrap()));
+    let weighted = CreateProposal {
+        weighted: Some(true),
+        ..proposal("Fund the treasury")
+    };
+    as_user(user(1), || create_proposal(1, weighted)).unwrap();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal(1).unwrap().approve, power * 2);
+}
+
+fn four_private_ballots() {
+    setup();
+    private_tally(1, proposal("Hire an auditor"));
+    for voter in 2..6 {
+        cast_private(1, user(voter), None).unwrap();
+    }
+}
+
+#[test]
+fn private_ballots_count_as_turnout() {
+    four_private_ballots();
+    assert_eq!(get_proposal_metrics(1).unwrap().turnout, 4);
+    assert_eq!(overall_participation(), 4.0);
+    assert_eq!(owner_stats(user(1)).avg_turnout, 4.0);
+}
+
+#[test]
+fn private_ballots_count_towards_revealing_history() {
+    four_private_ballots();
+    set_min_reveal_voters(4).unwrap();
+    let tally = Tally { approve: 4, ..Tally::default() };
+    assert_eq!(get_tally(1), Some(tally));
+    assert_eq!(get_tally_as_of(1, env::time()), Some(tally));
+    set_min_reveal_voters(5).unwrap();
+    assert_eq!(get_tally_as_of(1, env::time()), None);
+}
+
+#[test]
+fn delayed_reads_hide_recent_private_ballots() {
+    setup();
+    set_tally_delay(1_000).unwrap();
+    private_tally(1, proposal("Hire an auditor"));
+    cast_private(1, user(2), None).unwrap();
+    let proposal = get_proposal(1).unwrap();
+    assert_eq!((proposal.approve, proposal.anonymous_ballots), (0, 0));
+    env::advance_time(1_000);
+    let proposal = get_proposal(1).unwrap();
+    assert_eq!((proposal.approve, proposal.anonymous_ballots), (1, 1));
+}
@@ -0,0 +1,387 @@
+use super::*;
+
+#[test]
+fn description_policy_is_off_by_default() {
+    setup();
+    assert!(as_user(user(1), || create_proposal(1, proposal("aaaaaaa"))).is_ok());
+}
+
+#[test]
+fn description_policy_rejects_low_effort_text() {
+    setup();
+    set_description_policy(Some(4), Some(2)).unwrap();
+    assert_eq!(
+        as_user(user(1), || create_proposal(1, proposal("aaaaaaa"))).err(),
+        Some(VoteError::InvalidDescription)
+    );
+    assert!(as_user(user(1), || create_proposal(2, proposal("Increase treasury cap"))).is_ok());
+}
+
+#[test]
+fn description_policy_is_admin_only() {
+    setup();
+    assert_eq!(
+        as_user(user(1), || set_description_policy(Some(4), None)),
+        Err(VoteError::Unauthorized)
+    );
+}
+
+#[test]
+fn overwritten_proposal_starts_without_ballots_or_comments() {
+    setup();
+    create(1, user(1), "Fund the docs sprint");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(3), || add_comment(1, "Needs a budget".to_string())).unwrap();
+    create(1, user(1), "Fund the docs sprint, take two");
+    assert!(get_comments(1, 0, 10).is_empty());
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(get_proposal(1).unwrap().approve, 1);
+}
+
+// Longest description whose proposal, without a human id, just fits MAX_VALUE_SIZE.
+fn longest_description() -> String {
+    let probe = "x".repeat(200);
+    let size = Encode!(&new_proposal(proposal(&probe), user(1))).unwrap().len();
+    "x".repeat(200 + MAX_VALUE_SIZE as usize - size)
+}
+
+fn create_auto(prefix: &str, description: &str) -> Result<(u64, String), VoteError> {
+    as_user(user(1), || create_proposal_auto(prefix.to_string(), proposal(description)))
+}
+
+#[test]
+fn human_id_counts_towards_the_size_limit() {
+    setup();
+    let description = longest_description();
+    let mut value = new_proposal(proposal(&description), user(1));
+    value.human_id = Some("ab-001".to_string());
+    let size = Encode!(&value).unwrap().len() as u32;
+    let result = create_auto("ab", &description);
+    assert_eq!(result, Err(VoteError::ProposalTooLarge { size, max: MAX_VALUE_SIZE }));
+    assert_eq!(get_proposal_count(), 0);
+    let (key, human_id) = create_auto("ab", "Short one").unwrap();
+    assert_eq!(human_id, "ab-001");
+    assert_eq!(get_proposal(key).unwrap().human_id, Some(human_id));
+    assert!(as_user(user(1), || create_proposal(9, proposal(&description))).is_ok());
+}
+
+#[test]
+fn human_ids_resolve_to_their_proposal() {
+    setup();
+    let (first, first_id) = create_auto("GOV-2024", "Fund the park").unwrap();
+    let (second, second_id) = create_auto("GOV-2024", "Fund the pool").unwrap();
+    let (_, other_id) = create_auto("ops", "Fund the pier").unwrap();
+    assert_eq!((first_id.as_str(), second_id.as_str()), ("GOV-2024-001", "GOV-2024-002"));
+    assert_eq!(other_id, "ops-001");
+    let (key, found) = get_by_human_id(second_id).unwrap();
+    assert_eq!((key, found.description.as_str()), (second, "Fund the pool"));
+    assert_eq!(get_by_human_id(first_id).map(|(key, _)| key), Some(first));
+    assert!(get_by_human_id("GOV-2024-003".to_string()).is_none());
+    assert_eq!(create_auto("GOV 2024", "Fund the pond"), Err(VoteError::InvalidHumanId));
+    assert_eq!(create_auto("", "Fund the pond"), Err(VoteError::InvalidHumanId));
+}
+
+#[test]
+fn imported_voters_cannot_vote_again() {
+    setup();
+    let mut imported = new_proposal(proposal("Migrated from the old canister"), user(1));
+    imported.approve = 1;
+    imported.voted = vec![user(2)];
+    assert_eq!(import_proposals(vec![(1, imported)]), vec![Ok(())]);
+    assert_eq!(cast(1, user(2), VoteTypes::Reject), Err(VoteError::AlreadyVoted));
+    assert_eq!(cast(1, user(3), VoteTypes::Reject), Ok(()));
+    let stored = get_proposal(1).unwrap();
+    assert_eq!((stored.approve, stored.reject), (1, 1));
+}
+
+#[test]
+fn imported_counters_and_voters_read_back_exactly() {
+    setup();
+    let mut imported = new_proposal(proposal("Migrated from the old canister"), user(1));
+    (imported.approve, imported.reject, imported.pass) = (3, 2, 1);
+    imported.voted = (2..8).map(user).collect();
+    imported.human_id = Some("GOV-001".to_string());
+    assert_eq!(import_proposals(vec![(5, imported)]), vec![Ok(())]);
+    let stored = get_proposal(5).unwrap();
+    assert_eq!((stored.approve, stored.reject, stored.pass), (3, 2, 1));
+    assert_eq!(get_voters(5, 0, 10), (2..8).map(user).collect::<Vec<_>>());
+    assert_eq!(get_proposal_status(5), Some("Approved"));
+    // Minting skips the imported id, and each alias keeps its own proposal.
+    assert_eq!(create_auto("GOV", "Fund the park"), Ok((6, "GOV-002".to_string())));
+    assert_eq!(bulk_delete_proposals(vec![6]), vec![Ok(())]);
+    assert_eq!(get_by_human_id("GOV-001".to_string()).map(|(key, _)| key), Some(5));
+}
+
+#[test]
+fn descriptions_match_regardless_of_case_and_spacing() {
+    assert_eq!(description_hash("Fund  the\tPark "), description_hash("fund the park"));
+    assert_ne!(description_hash("fund the park"), description_hash("fund the parks"));
+}
+
+#[test]
+fn duplicate_descriptions_are_refused_while_the_first_is_active() {
+    setup();
+    set_reject_duplicate_descriptions(true).unwrap();
+    create(1, user(1), "Fund the park");
+    let duplicate = try_create(2, user(2), proposal("fund  the PARK"));
+    assert_eq!(duplicate, Err(VoteError::DuplicateProposal));
+    as_user(user(1), || end_proposal(1)).unwrap();
+    create(2, user(2), "fund  the PARK");
+}
+
+#[test]
+fn active_limit_is_freed_by_closing_a_proposal() {
+    setup();
+    set_max_active_proposals(Some(2)).unwrap();
+    create(1, user(1), "Fund the park");
+    create(2, user(1), "Fund the pool");
+    let over = try_create(3, user(1), proposal("Fund the pier"));
+    assert_eq!(over, Err(VoteError::ActiveLimitReached));
+    let draft = CreateProposal {
+        is_active: false,
+        ..proposal("Fund the pier")
+    };
+    assert_eq!(try_create(3, user(1), draft), Ok(()));
+    assert_eq!(as_user(user(1), || activate_proposal(3)), Err(VoteError::ActiveLimitReached));
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(as_user(user(1), || activate_proposal(3)), Ok(()));
+    assert_eq!(ACTIVE_COUNT.with(|c| *c.borrow().get()), 2);
+}
+
+#[test]
+fn active_limit_is_off_by_default() {
+    setup();
+    for key in 0..5 {
+        create(key, user(1), &format!("Fund park {key}"));
+    }
+    assert_eq!(ACTIVE_COUNT.with(|c| *c.borrow().get()), 5);
+}
+
+#[test]
+fn would_fit_checks_the_projected_size() {
+    assert!(would_fit(proposal("Fund the park"), 100));
+    let huge = "x".repeat(MAX_VALUE_SIZE as usize);
+    assert!(!would_fit(proposal(&huge), 0));
+    assert!(!would_fit(proposal("Fund the park"), MAX_VALUE_SIZE / 8));
+    assert!(!would_fit(proposal("Fund the park"), u32::MAX));
+}
+
+#[test]
+fn owners_are_recognised() {
+    setup();
+    create(1, user(1), "Fund the park");
+    assert_eq!(get_owner(1), Some(user(1)));
+    assert!(as_user(user(1), || is_owner(1)));
+    assert!(!as_user(user(2), || is_owner(1)));
+    assert_eq!(get_owner(2), None);
+    assert!(!as_user(user(1), || is_owner(2)));
+}
+
+fn attachment(name: &str, url_len: usize) -> Attachment {
+    Attachment {
+        name: name.to_string(),
+        sha256: [name.len() as u8; 32],
+        url: "u".repeat(url_len),
+    }
+}
+
+fn with_attachments(description: &str, attachments: Vec<Attachment>) -> CreateProposal {
+    CreateProposal {
+        attachments: Some(attachments),
+        ..proposal(description)
+    }
+}
+
+#[test]
+fn attachments_are_read_back() {
+    setup();
+    let docs = vec![attachment("spec.pdf", 20), attachment("budget.csv", 30)];
+    assert_eq!(try_create(1, user(1), with_attachments("Fund the park", docs)), Ok(()));
+    let stored = get_attachments(1).unwrap();
+    let read: Vec<_> = stored
+        .iter()
+        .map(|a| (a.name.as_str(), a.sha256[0], a.url.len()))
+        .collect();
+    assert_eq!(read, vec![("spec.pdf", 8, 20), ("budget.csv", 10, 30)]);
+    assert!(get_attachments(2).is_none());
+}
+
+#[test]
+fn attachments_are_bounded() {
+    let full = vec![attachment("n", MAX_ATTACHMENT_URL_LEN); MAX_ATTACHMENTS];
+    assert!(validate_attachments(&Some(full.clone())).is_ok());
+    let too_many = vec![attachment("n", 1); MAX_ATTACHMENTS + 1];
+    assert_eq!(validate_attachments(&Some(too_many)), Err(VoteError::InvalidAttachment));
+    let long_url = vec![attachment("n", MAX_ATTACHMENT_URL_LEN + 1)];
+    assert_eq!(validate_attachments(&Some(long_url)), Err(VoteError::InvalidAttachment));
+    let long_name = vec![attachment(&"n".repeat(MAX_ATTACHMENT_NAME_LEN + 1), 1)];
+    assert_eq!(validate_attachments(&Some(long_name)), Err(VoteError::InvalidAttachment));
+    let unnamed = vec![attachment("", 1)];
+    assert_eq!(validate_attachments(&Some(unnamed)), Err(VoteError::InvalidAttachment));
+    setup();
+    let description = longest_description();
+    let value = new_proposal(with_attachments(&description, full.clone()), user(1));
+    let size = Encode!(&value).unwrap().len() as u32;
+    let oversized = try_create(1, user(1), with_attachments(&description, full));
+    assert_eq!(oversized, Err(VoteError::ProposalTooLarge { size, max: MAX_VALUE_SIZE }));
+    assert!(get_proposal(1).is_none());
+}
+
+#[test]
+fn edits_past_the_size_limit_are_refused() {
+    setup();
+    let description = longest_description();
+    create(1, user(1), &description);
+    let longer = proposal(&format!("{description}x"));
+    let edited = as_user(user(1), || edit_proposal(1, longer));
+    let size = MAX_VALUE_SIZE + 1;
+    assert_eq!(edited, Err(VoteError::ProposalTooLarge { size, max: MAX_VALUE_SIZE }));
+    assert_eq!(get_proposal(1).unwrap().description, description);
+}
+
+#[test]
+fn voters_past_the_size_limit_are_refused() {
+    setup();
+    // Room for one more ten-byte principal, which takes 12 bytes in the voter list.
+    let description = longest_description();
+    create(1, user(1), &description[20..]);
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    let size = MAX_VALUE_SIZE - 20 + 24;
+    let refused = cast(1, user(3), VoteTypes::Approve);
+    assert_eq!(refused, Err(VoteError::ProposalTooLarge { size, max: MAX_VALUE_SIZE }));
+    let stored = get_proposal(1).unwrap();
+    assert_eq!((stored.approve, stored.voted), (1, vec![user(2)]));
+    assert_eq!(get_remaining_allowance(1, user(3)), 1);
+}
+
+#[test]
+fn leading_zero_bits_stop_at_the_first_set_bit() {
+    assert_eq!(leading_zero_bits(&[0xff]), 0);
+    assert_eq!(leading_zero_bits(&[0x00, 0x10, 0x00]), 11);
+    assert_eq!(leading_zero_bits(&[0x01, 0x00]), 7);
+    assert_eq!(leading_zero_bits(&[0x00; 4]), 32);
+}
+
+fn pow_bits(owner: Principal, key: u64, nonce: u64) -> u32 {
+    let mut hasher = Sha256::new();
+    hasher.update(owner.as_slice());
+    hasher.update(key.to_be_bytes());
+    hasher.update(nonce.to_be_bytes());
+    leading_zero_bits(&hasher.finalize())
+}
+
+fn create_pow(key: u64, nonce: u64) -> Result<(), VoteError> {
+    as_user(user(1), || create_proposal_pow(key, proposal("Fund the park"), nonce)).map(|_| ())
+}
+
+#[test]
+fn proof_of_work_is_checked_against_the_difficulty() {
+    setup();
+    set_pow_difficulty(Some(8)).unwrap();
+    let valid = (0..).find(|nonce| pow_bits(user(1), 1, *nonce) >= 8).unwrap();
+    let invalid = (0..).find(|nonce| pow_bits(user(1), 1, *nonce) < 8).unwrap();
+    assert_eq!(create_pow(1, invalid), Err(VoteError::InvalidProofOfWork));
+    let plain = try_create(1, user(1), proposal("Fund the park"));
+    assert_eq!(plain, Err(VoteError::InvalidProofOfWork));
+    assert_eq!(create_pow(1, valid), Ok(()));
+    // The nonce is bound to the key it was solved for.
+    assert!(pow_bits(user(1), 2, valid) < 8);
+    assert_eq!(create_pow(2, valid), Err(VoteError::InvalidProofOfWork));
+}
+
+#[test]
+fn owners_cannot_repeat_their_own_descriptions() {
+    setup();
+    set_owner_unique_descriptions(true).unwrap();
+    create(1, user(1), "Fund the park");
+    as_user(user(1), || end_proposal(1)).unwrap();
+    let repeated = try_create(2, user(1), proposal("fund the  park"));
+    assert_eq!(repeated, Err(VoteError::DuplicateProposal));
+    assert_eq!(try_create(2, user(2), proposal("Fund the park")), Ok(()));
+    // Overwriting a proposal with its own description is not a duplicate.
+    assert_eq!(try_create(1, user(1), proposal("Fund the park")), Ok(()));
+}
+
+#[test]
+fn proposal_hash_covers_content_but_not_votes() {
+    setup();
+    create(1, user(1), "Fund the park");
+    let created = get_proposal_hash(1).unwrap();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(10);
+    assert_eq!(get_proposal_hash(1), Some(created));
+    as_user(user(1), || edit_proposal(1, proposal("Fund the new park"))).unwrap();
+    let edited = get_proposal_hash(1).unwrap();
+    assert_ne!(edited, created);
+    as_user(user(1), || set_threshold(1, 70)).unwrap();
+    let stricter = get_proposal_hash(1).unwrap();
+    assert_ne!(stricter, edited);
+    as_user(user(1), || end_proposal(1)).unwrap();
+    assert_eq!(get_proposal_hash(1), Some(stricter));
+    assert_eq!(get_proposal_hash(2), None);
+}
+
+#[test]
+fn transferred_proposals_are_editable_by_the_new_owner() {
+    setup();
+    for key in 1..=3 {
+        create(key, user(1), &format!("Fund park {key}"));
+    }
+    create(4, user(2), "Fund the pool");
+    let refused = as_user(user(1), || transfer_all_proposals(user(1), user(3)));
+    assert_eq!(refused, Err(VoteError::Unauthorized));
+    assert_eq!(transfer_all_proposals(user(1), user(3)), Ok(3));
+    for key in 1..=3 {
+        assert_eq!(get_owner(key), Some(user(3)));
+        let edit = || edit_proposal(key, proposal(&format!("Fund new park {key}")));
+        assert_eq!(as_user(user(1), edit), Err(VoteError::Unauthorized));
+        assert_eq!(as_user(user(3), edit), Ok(()));
+    }
+    assert_eq!(get_owner(4), Some(user(2)));
+    assert_eq!(count_active_by_owner(user(3)), 3);
+    assert_eq!(transfer_all_proposals(user(1), user(3)), Ok(0));
+}
+
+#[test]
+fn locked_proposals_are_editable_until_the_first_vote() {
+    setup();
+    let locked = CreateProposal {
+        lock_on_first_vote: Some(true),
+        ..proposal("Fund the park")
+    };
+    assert_eq!(try_create(1, user(1), locked), Ok(()));
+    create(2, user(1), "Fund the pool");
+    let edit = |key| as_user(user(1), || edit_proposal(key, proposal("Fund it all")));
+    assert_eq!(edit(1), Ok(()));
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(edit(1), Err(VoteError::ProposalLocked));
+    assert_eq!(get_proposal(1).unwrap().description, "Fund it all");
+    assert!(get_proposal(1).unwrap().lock_on_first_vote);
+    assert_eq!(edit(2), Ok(()));
+}
+
+#[test]
+fn owners_wait_out_the_create_interval() {
+    setup();
+    set_min_create_interval(1_000).unwrap();
+    assert_eq!(try_create(1, user(1), proposal("Fund the park")), Ok(()));
+    assert_eq!(try_create(2, user(1), proposal("Fund the pool")), Err(VoteError::RateLimited));
+    assert_eq!(try_create(2, user(2), proposal("Fund the pool")), Ok(()));
+    env::advance_time(999);
+    assert_eq!(try_create(3, user(1), proposal("Fund the pool")), Err(VoteError::RateLimited));
+    env::advance_time(1);
+    assert_eq!(try_create(3, user(1), proposal("Fund the pool")), Ok(()));
+}
+
+#[test]
+fn only_the_owner_starts_a_live_proposal_over() {
+    setup();
+    create(1, user(1), "Fund the docs sprint");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    let taken = try_create(1, user(6), proposal("Fund my own sprint"));
+    assert_eq!(taken, Err(VoteError::ProposalAlreadyExists));
+    let stored = get_proposal(1).unwrap();
+    assert_eq!((stored.owner, stored.approve), (user(1), 1));
+    assert_eq!(get_voters(1, 0, 10), vec![user(2)]);
+}
@@ -0,0 +1,47 @@
+use super::*;
+
+fn finalize(key: u64, who: Principal, description: &str) -> Result<(), VoteError> {
+    as_user(who, || finalize_reservation(key, proposal(description)))
+}
+
+#[test]
+fn reservations_expire_after_their_ttl() {
+    let reservation = Reservation {
+        owner: user(1),
+        reserved_at: 100,
+    };
+    assert!(!reservation.is_expired(100 + RESERVATION_TTL_NS));
+    assert!(reservation.is_expired(101 + RESERVATION_TTL_NS));
+    assert!(!reservation.is_expired(0));
+}
+
+#[test]
+fn reserved_keys_are_finalized_once() {
+    setup();
+    create(0, user(1), "Fund the park");
+    let key = as_user(user(1), reserve_key).unwrap();
+    assert_eq!(key, 1);
+    assert_eq!(as_user(user(2), reserve_key), Ok(2));
+    let taken = try_create(1, user(2), proposal("Take the key"));
+    assert_eq!(taken, Err(VoteError::ProposalAlreadyExists));
+    assert_eq!(finalize(1, user(2), "Fund the pool"), Err(VoteError::Unauthorized));
+    // A refused proposal leaves the reservation for a retry.
+    set_description_policy(Some(4), None).unwrap();
+    assert_eq!(finalize(1, user(1), "aaaaaaa"), Err(VoteError::InvalidDescription));
+    assert_eq!(finalize(1, user(1), "Fund the pool"), Ok(()));
+    assert_eq!(get_proposal(1).unwrap().description, "Fund the pool");
+    assert_eq!(finalize(1, user(1), "Fund the pool"), Err(VoteError::NoReservation));
+}
+
+#[test]
+fn stale_reservations_are_swept() {
+    setup();
+    as_user(user(1), reserve_key).unwrap();
+    env::advance_time(RESERVATION_TTL_NS);
+    as_user(user(2), reserve_key).unwrap();
+    env::advance_time(1);
+    assert_eq!(finalize(0, user(1), "Fund the park"), Err(VoteError::NoReservation));
+    assert_eq!(as_user(user(1), sweep_reservations), Err(VoteError::Unauthorized));
+    assert_eq!(sweep_reservations(), Ok(1));
+    assert_eq!(finalize(1, user(2), "Fund the pool"), Ok(()));
+}
@@ -0,0 +1,59 @@
+use super::*;
+
+// Proposal 1, already Approved on its counters but below the reveal threshold of 3.
+fn two_approvals() {
+    setup();
+    set_min_reveal_voters(3).unwrap();
+    set_record_vote_timeline(true).unwrap();
+    create(1, user(1), "Adopt the new logo");
+    as_user(user(1), || set_quorum(1, 1)).unwrap();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Approve).unwrap();
+}
+
+fn counters(proposal: &Proposal) -> (u64, u64, u64) {
+    (proposal.approve, proposal.reject, proposal.pass)
+}
+
+#[test]
+fn hidden_counts_are_blank_in_every_listing() {
+    two_approvals();
+    assert_eq!(counters(&get_proposal(1).unwrap()), (0, 0, 0));
+    assert_eq!(counters(&list_proposals(0, 10, false)[0].1), (0, 0, 0));
+    assert_eq!(counters(&search("logo".to_string(), 0, 10).unwrap()[0].1), (0, 0, 0));
+    assert_eq!(counters(&get_proposals_in_range(0, 5, 10)[0].1), (0, 0, 0));
+    let (_, proposal, status) = &list_with_status(0, 10)[0];
+    assert_eq!((counters(proposal), *status), ((0, 0, 0), ProposalStatus::Undecided));
+}
+
+#[test]
+fn hidden_counts_stay_out_of_derived_reads() {
+    two_approvals();
+    assert!(get_proposal_metrics(1).is_none());
+    assert!(get_voters_by_choice(1, VoteTypes::Approve).is_empty());
+    assert_eq!(get_undecided_proposals(0, 10), vec![(1, 0)]);
+    assert!(get_vote_timeline(1, 0, 10).is_empty());
+    assert!(get_tally_buckets(1, 1_000).is_empty());
+    let events = as_user(user(9), || get_proposal_events(1, 0, 10));
+    assert!(!events.iter().any(|event| matches!(event.kind, EventKind::Voted { .. })));
+    let receipt = as_user(user(4), || block_on(vote_with_receipt(1, VoteTypes::Reject, None)));
+    assert_eq!(receipt.unwrap().tally_after, Tally { approve: 2, reject: 1, pass: 0 });
+}
+
+#[test]
+fn receipt_of_a_hidden_vote_is_blank() {
+    two_approvals();
+    set_min_reveal_voters(4).unwrap();
+    let receipt = as_user(user(4), || block_on(vote_with_receipt(1, VoteTypes::Reject, None)));
+    assert_eq!(receipt.unwrap().tally_after, Tally::default());
+}
+
+#[test]
+fn counts_show_once_enough_voted() {
+    two_approvals();
+    cast(1, user(4), VoteTypes::Pass).unwrap();
+    assert_eq!(counters(&get_proposal(1).unwrap()), (2, 0, 1));
+    assert_eq!(get_voters_by_choice(1, VoteTypes::Approve), vec![user(2), user(3)]);
+    assert_eq!(get_vote_timeline(1, 0, 10).len(), 3);
+    assert!(get_undecided_proposals(0, 10).is_empty());
+}
@@ -0,0 +1,62 @@
+use super::*;
+
+// DiffChunk is only ever encoded, so compare it as (marker, line).
+fn marked(chunks: Vec<DiffChunk>) -> Vec<(char, String)> {
+    chunks
+        .into_iter()
+        .map(|chunk| match chunk {
+            DiffChunk::Unchanged(line) => (' ', line),
+            DiffChunk::Added(line) => ('+', line),
+            DiffChunk::Removed(line) => ('-', line),
+        })
+        .collect()
+}
+
+fn lines(expected: &[(char, &str)]) -> Vec<(char, String)> {
+    expected.iter().map(|(mark, line)| (*mark, line.to_string())).collect()
+}
+
+#[test]
+fn diff_keeps_common_lines_and_marks_the_rest() {
+    let diff = marked(diff_lines("a\nb\nc\nd", "a\nc\nx\nd\ne"));
+    let expected = [(' ', "a"), ('-', "b"), (' ', "c"), ('+', "x"), (' ', "d"), ('+', "e")];
+    assert_eq!(diff, lines(&expected));
+    assert_eq!(marked(diff_lines("same", "same")), lines(&[(' ', "same")]));
+    assert_eq!(marked(diff_lines("", "new")), lines(&[('+', "new")]));
+    assert_eq!(marked(diff_lines("old", "")), lines(&[('-', "old")]));
+}
+
+#[test]
+fn stored_revisions_are_diffed() {
+    setup();
+    create(1, user(1), "Fund the park\nBudget: 10k");
+    let edit = proposal("Fund the park\nBudget: 12k\nStart in May");
+    as_user(user(1), || edit_proposal(1, edit)).unwrap();
+    assert_eq!(history_count(1), 2);
+    let diff = marked(get_description_diff(1, 0, 1).unwrap());
+    let expected = [
+        (' ', "Fund the park"),
+        ('-', "Budget: 10k"),
+        ('+', "Budget: 12k"),
+        ('+', "Start in May"),
+    ];
+    assert_eq!(diff, lines(&expected));
+    assert!(get_description_diff(1, 0, 2).is_none());
+    assert!(get_description_diff(2, 0, 0).is_none());
+}
+
+#[test]
+fn only_the_latest_revisions_are_kept() {
+    setup();
+    set_max_description_revisions(3).unwrap();
+    create(1, user(1), "Budget: 10k");
+    for budget in 11..15 {
+        let edit = proposal(&format!("Budget: {budget}k"));
+        as_user(user(1), || edit_proposal(1, edit)).unwrap();
+    }
+    assert_eq!(history_count(1), 3);
+    assert!(get_description_diff(1, 1, 4).is_none());
+    let diff = marked(get_description_diff(1, 2, 4).unwrap());
+    assert_eq!(diff, lines(&[('-', "Budget: 12k"), ('+', "Budget: 14k")]));
+    assert_eq!(history_count(2), 0);
+}
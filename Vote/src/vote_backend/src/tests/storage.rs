@@ -0,0 +1,71 @@
+use super::*;
+
+// Stands in for a value written by a build whose schema no longer decodes.
+struct Garbage;
+
+impl Storable for Garbage {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&[0xff; 8])
+    }
+
+    fn from_bytes(_: Cow<[u8]>) -> Self {
+        Garbage
+    }
+}
+
+impl BoundedStorable for Garbage {
+    const MAX_SIZE: u32 = MAX_VALUE_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Must run before anything touches PROPOSAL_MAP, which would cache the empty tree.
+fn corrupt_proposal(key: u64) {
+    let memory = MEMORY_MANAGER.with(|m| m.borrow().get(memory::PROPOSAL_MAP));
+    let mut raw: StableBTreeMap<u64, Garbage, Memory> = StableBTreeMap::init(memory);
+    raw.insert(key, Garbage);
+}
+
+#[test]
+#[should_panic(expected = "at key 42 in stable memory (8 bytes)")]
+fn corrupt_proposal_names_its_key() {
+    setup();
+    corrupt_proposal(42);
+    get_proposal(42);
+}
+
+#[test]
+#[should_panic(expected = "corrupt vote_backend::StorableString<16> in stable memory (2 bytes)")]
+fn invalid_utf8_string_names_its_type() {
+    StorableString::<16>::from_bytes(Cow::Borrowed(&[0xc3, 0x28]));
+}
+
+#[test]
+fn integrity_check_flags_drifted_counters_and_orphans() {
+    setup();
+    create(1, user(1), "Keep the archive");
+    create(2, user(1), "Drop the archive");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    let clean = verify_integrity().ok().unwrap();
+    assert_eq!(clean.scanned, 2);
+    assert!(clean.tally_mismatch.is_empty() && clean.orphaned_participation.is_empty());
+    PROPOSAL_MAP.with(|p| {
+        let mut proposal = p.borrow().get(&2).unwrap();
+        proposal.reject = 4;
+        p.borrow_mut().insert(2, proposal);
+    });
+    PARTICIPATION_PERCENTAGE_MAP.with(|p| p.borrow_mut().insert(9, 50));
+    let report = verify_integrity().ok().unwrap();
+    assert_eq!(report.tally_mismatch, vec![2]);
+    assert_eq!(report.orphaned_participation, vec![9]);
+    assert!(report.oversized.is_empty());
+    assert_eq!(as_user(user(1), verify_integrity).err(), Some(VoteError::Unauthorized));
+}
+
+#[test]
+fn memory_ids_are_allocated_once() {
+    let ids: std::collections::BTreeSet<u8> = memory::ALLOCATED.iter().copied().collect();
+    assert_eq!(ids.len(), memory::ALLOCATED.len());
+    assert!(memory::all_unique(&[0, 1, 2]));
+    assert!(!memory::all_unique(&[0, 1, 0]));
+}
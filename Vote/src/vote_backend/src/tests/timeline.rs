@@ -0,0 +1,100 @@
+use super::*;
+
+fn recording() {
+    setup();
+    set_record_vote_timeline(true).unwrap();
+    create(1, user(1), "Choose the logo");
+}
+
+#[test]
+fn timeline_lists_ballots_in_order() {
+    recording();
+    let choices = [VoteTypes::Reject, VoteTypes::Approve, VoteTypes::Pass];
+    for (voter, choice) in (10..).zip(choices) {
+        env::advance_time(10);
+        cast(1, user(voter), choice).unwrap();
+    }
+    let start = env::START_TIME;
+    assert_eq!(
+        get_vote_timeline(1, 0, 10),
+        vec![
+            (start + 10, VoteTypes::Reject),
+            (start + 20, VoteTypes::Approve),
+            (start + 30, VoteTypes::Pass),
+        ]
+    );
+    assert_eq!(get_vote_timeline(1, 2, 10), vec![(start + 30, VoteTypes::Pass)]);
+}
+
+#[test]
+fn timeline_is_off_by_default() {
+    setup();
+    create(1, user(1), "Choose the logo");
+    cast(1, user(10), VoteTypes::Approve).unwrap();
+    assert!(get_vote_timeline(1, 0, 10).is_empty());
+}
+
+#[test]
+fn timeline_stops_growing_at_its_bound() {
+    recording();
+    VOTE_TIMELINE.with(|t| {
+        let last = TimelineEntry {
+            timestamp: env::time(),
+            choice: VoteTypes::Approve,
+            weight: 1,
+        };
+        t.borrow_mut().insert((1, MAX_TIMELINE_LEN - 1), last);
+    });
+    cast(1, user(10), VoteTypes::Reject).unwrap();
+    let len = VOTE_TIMELINE.with(|t| t.borrow().range((1, 0)..=(1, u64::MAX)).count());
+    assert_eq!(len, 1);
+    assert_eq!(get_proposal(1).unwrap().reject, 1);
+}
+
+fn counts(approve: u64, reject: u64, pass: u64) -> Tally {
+    Tally {
+        approve,
+        reject,
+        pass,
+    }
+}
+
+#[test]
+fn buckets_sum_the_ballots_of_each_window() {
+    recording();
+    let start = env::START_TIME;
+    let ballots = [
+        (100, VoteTypes::Approve),
+        (200, VoteTypes::Reject),
+        (1_500, VoteTypes::Approve),
+        (3_100, VoteTypes::Pass),
+    ];
+    for (voter, (at, choice)) in (10..).zip(ballots) {
+        env::advance_time(start + at - env::time());
+        cast(1, user(voter), choice).unwrap();
+    }
+    // The empty window at start + 2000 is skipped.
+    assert_eq!(
+        get_tally_buckets(1, 1_000),
+        vec![
+            (start, counts(1, 1, 0)),
+            (start + 1_000, counts(1, 0, 0)),
+            (start + 3_000, counts(0, 0, 1)),
+        ]
+    );
+    assert!(get_tally_buckets(1, 0).is_empty());
+    assert!(get_tally_buckets(2, 1_000).is_empty());
+}
+
+#[test]
+fn a_new_round_starts_an_empty_timeline() {
+    recording();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert!(as_user(user(1), || snapshot_and_reset(1)).is_ok());
+    env::advance_time(10);
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    let timeline = vec![(env::START_TIME + 10, VoteTypes::Reject)];
+    assert_eq!(get_vote_timeline(1, 0, 10), timeline);
+    let buckets = get_tally_buckets(1, 1_000);
+    assert_eq!(buckets.iter().map(|(_, tally)| tally.approve).sum::<u64>(), 0);
+}
@@ -0,0 +1,379 @@
+use super::*;
+use legacy::LegacyStored;
+
+// Proposals as the first release stored them, in a map with 100-byte values.
+#[derive(CandidType)]
+struct FirstProposal {
+    description: String,
+    approve: u32,
+    reject: u32,
+    pass: u32,
+    is_active: bool,
+    voted: Vec<Principal>,
+    owner: Principal,
+}
+
+// A release in between, with a few settings already but not all of them.
+#[derive(CandidType)]
+struct LaterProposal {
+    description: String,
+    approve: u32,
+    reject: u32,
+    pass: u32,
+    is_active: bool,
+    voted: Vec<Principal>,
+    owner: Principal,
+    private_voters: bool,
+    created_at: u64,
+    updated_at: u64,
+}
+
+#[derive(CandidType)]
+struct FirstConfig {
+    admin: Principal,
+    min_description_distinct_chars: Option<u32>,
+    min_description_words: Option<u32>,
+}
+
+#[derive(CandidType)]
+struct FirstVoteRecord {
+    choice: VoteTypes,
+    timestamp: u64,
+}
+
+#[derive(CandidType)]
+struct WeightedVoteRecord {
+    choice: VoteTypes,
+    timestamp: u64,
+    weight: u32,
+}
+
+fn raw_map<K: BoundedStorable + Ord + Clone, const MAX: u32>(
+    id: ic_stable_structures::memory_manager::MemoryId,
+) -> StableBTreeMap<K, LegacyStored<MAX>, Memory> {
+    StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(id)))
+}
+
+fn stored<const MAX: u32>(value: impl CandidType) -> LegacyStored<MAX> {
+    LegacyStored(Encode!(&value).unwrap())
+}
+
+fn first_proposal(description: &str, approve: u32) -> FirstProposal {
+    FirstProposal {
+        description: description.to_string(),
+        approve,
+        reject: 0,
+        pass: 0,
+        is_active: true,
+        voted: (0..approve as u8).map(|n| user(100 + n)).collect(),
+        owner: user(1),
+    }
+}
+
+fn upgrade() {
+    env::set_caller(admin());
+    post_upgrade();
+}
+
+#[test]
+fn first_release_proposals_move_to_the_current_map() {
+    let mut old = raw_map::<u64, 100>(memory::LEGACY_PROPOSAL_MAP);
+    old.insert(1, stored(first_proposal("Trees", 1)));
+    old.insert(2, stored(first_proposal("Paint the hall", 0)));
+    upgrade();
+    let proposal = get_proposal(1).unwrap();
+    assert_eq!((proposal.description.as_str(), proposal.approve), ("Trees", 1));
+    assert!(proposal.listed && proposal.owner_vote_counts);
+    assert_eq!(get_proposal_count(), 2);
+    assert_eq!(ACTIVE_COUNT.with(|c| *c.borrow().get()), 2);
+    assert!(raw_map::<u64, 100>(memory::LEGACY_PROPOSAL_MAP).is_empty());
+    assert_eq!(cast(2, user(9), VoteTypes::Approve), Ok(()));
+}
+
+#[test]
+fn first_release_upgrader_becomes_admin() {
+    let mut old = raw_map::<u64, 100>(memory::LEGACY_PROPOSAL_MAP);
+    old.insert(1, stored(first_proposal("Trees", 0)));
+    upgrade();
+    assert!(is_admin(&admin()));
+    assert_eq!(config().votes_per_principal, 1);
+}
+
+#[test]
+fn archived_proposals_of_a_later_release_move_too() {
+    let later = LaterProposal {
+        description: "Repair the roof".to_string(),
+        approve: 1,
+        reject: 0,
+        pass: 0,
+        is_active: false,
+        voted: vec![user(2)],
+        owner: user(1),
+        private_voters: true,
+        created_at: 7,
+        updated_at: 8,
+    };
+    raw_map::<u64, MAX_VALUE_SIZE>(memory::LEGACY_ARCHIVE_MAP).insert(4, stored(later));
+    upgrade();
+    let archived = ARCHIVE_MAP.with(|a| a.borrow().get(&4)).unwrap();
+    assert!(archived.private_voters);
+    assert_eq!((archived.created_at, archived.updated_at), (7, 8));
+    assert_eq!(archived.voted, vec![user(2)]);
+}
+
+#[test]
+fn config_of_an_older_release_keeps_its_settings() {
+    let first = FirstConfig {
+        admin: user(7),
+        min_description_distinct_chars: Some(4),
+        min_description_words: None,
+    };
+    let memory = MEMORY_MANAGER.with(|m| m.borrow().get(memory::CONFIG));
+    StableCell::init(memory, LegacyStored::<0>(Encode!(&first).unwrap())).unwrap();
+    upgrade();
+    let config = config();
+    assert_eq!(config.admin, user(7));
+    assert_eq!(config.min_description_distinct_chars, Some(4));
+    let default = CanisterConfig::default();
+    assert_eq!(config.vote_cancellation_window_ns, default.vote_cancellation_window_ns);
+}
+
+#[test]
+fn vote_records_of_older_releases_still_read() {
+    let mut records = raw_map::<(u64, StorablePrincipal), 128>(memory::VOTE_MAP);
+    let first = FirstVoteRecord {
+        choice: VoteTypes::Reject,
+        timestamp: 5,
+    };
+    let weighted = WeightedVoteRecord {
+        choice: VoteTypes::Approve,
+        timestamp: 6,
+        weight: 3,
+    };
+    records.insert((1, StorablePrincipal(user(2))), stored(first));
+    records.insert((1, StorablePrincipal(user(3))), stored(weighted));
+    let read = |who: u8| {
+        VOTE_MAP.with(|v| v.borrow().get(&(1, StorablePrincipal(user(who))))).unwrap()
+    };
+    let (first, weighted) = (read(2), read(3));
+    assert_eq!((first.cast, first.ballots), (Tally { approve: 0, reject: 1, pass: 0 }, 1));
+    assert_eq!(weighted.cast, Tally { approve: 3, reject: 0, pass: 0 });
+}
+
+#[derive(CandidType)]
+enum FirstEventKind {
+    Created,
+    Edited,
+    Voted { choice: VoteTypes, weight: u32 },
+    VoteCancelled,
+    VotesInvalidated,
+    Ended,
+    Activated,
+}
+
+#[derive(CandidType)]
+struct FirstEvent {
+    proposal_key: u64,
+    caller: Principal,
+    timestamp: u64,
+    kind: FirstEventKind,
+}
+
+#[test]
+fn events_without_activity_still_read() {
+    let mut log = raw_map::<u64, 256>(memory::EVENT_LOG);
+    let kinds = [
+        FirstEventKind::Created,
+        FirstEventKind::Edited,
+        FirstEventKind::Voted {
+            choice: VoteTypes::Pass,
+            weight: 1,
+        },
+        FirstEventKind::VoteCancelled,
+        FirstEventKind::VotesInvalidated,
+        FirstEventKind::Ended,
+        FirstEventKind::Activated,
+    ];
+    for (seq, kind) in kinds.into_iter().enumerate() {
+        let event = FirstEvent {
+            proposal_key: 1,
+            caller: user(1),
+            timestamp: seq as u64,
+            kind,
+        };
+        log.insert(seq as u64, stored(event));
+    }
+    upgrade();
+    let kinds: Vec<_> = get_proposal_events(1, 0, 10).into_iter().map(|event| event.kind).collect();
+    assert!(matches!(
+        kinds.as_slice(),
+        [
+            EventKind::Created { is_active: true },
+            EventKind::Edited { is_active: true },
+            EventKind::Voted {
+                choice: VoteTypes::Pass,
+                weight: 1
+            },
+            EventKind::VoteCancelled,
+            EventKind::VotesInvalidated,
+            EventKind::Ended,
+            EventKind::Activated,
+        ]
+    ));
+}
+
+// An event from before ballots recorded whether their voter was private.
+#[derive(CandidType)]
+struct UnflaggedEvent {
+    proposal_key: u64,
+    caller: Principal,
+    timestamp: u64,
+    kind: EventKind,
+}
+
+#[test]
+fn unflagged_ballots_of_deleted_proposals_are_redacted() {
+    let mut log = raw_map::<u64, 256>(memory::EVENT_LOG);
+    for key in [1, 2] {
+        let event = UnflaggedEvent {
+            proposal_key: key,
+            caller: user(2),
+            timestamp: 0,
+            kind: EventKind::Voted {
+                choice: VoteTypes::Approve,
+                weight: 1,
+            },
+        };
+        log.insert(key - 1, stored(event));
+    }
+    upgrade();
+    create(1, user(1), "Publish the roadmap");
+    let callers = |key| as_user(user(3), || get_proposal_events(key, 0, 10))[0].caller;
+    assert_eq!(callers(1), user(2));
+    assert_eq!(callers(2), Principal::anonymous());
+}
+
+// `VoteError` while `ProposalTooLarge` had no payload, cut down to what the test stores.
+#[derive(CandidType)]
+enum FirstVoteError {
+    ProposalNotActive,
+    ProposalTooLarge,
+}
+
+#[derive(CandidType)]
+struct FirstIdempotentVote {
+    key_hash: [u8; 32],
+    result: Result<(), FirstVoteError>,
+}
+
+fn key_hash(key: u64, idempotency_key: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(key.to_be_bytes());
+    hasher.update(idempotency_key);
+    hasher.finalize().into()
+}
+
+#[test]
+fn idempotent_votes_of_older_releases_still_replay() {
+    setup();
+    create(1, user(1), "Trees");
+    let mut entries = raw_map::<StorablePrincipal, 1024>(memory::IDEMPOTENCY_MAP);
+    let replayed = |result| FirstIdempotentVote {
+        key_hash: key_hash(1, b"k"),
+        result,
+    };
+    entries.insert(StorablePrincipal(user(2)), stored(replayed(Ok(()))));
+    entries.insert(
+        StorablePrincipal(user(3)),
+        stored(replayed(Err(FirstVoteError::ProposalNotActive))),
+    );
+    entries.insert(
+        StorablePrincipal(user(4)),
+        stored(replayed(Err(FirstVoteError::ProposalTooLarge))),
+    );
+    let retry = |who| {
+        as_user(user(who), || block_on(vote(1, VoteTypes::Approve, None, Some(b"k".to_vec()))))
+    };
+    assert_eq!(retry(2), Ok(()));
+    assert_eq!(retry(3), Err(VoteError::VoteFailed));
+    assert_eq!(retry(4), Err(VoteError::VoteFailed));
+    assert_eq!(get_proposal(1).unwrap().approve, 0);
+}
+
+#[derive(CandidType)]
+struct NarrowTally {
+    approve: u32,
+    reject: u32,
+    pass: u32,
+}
+
+#[derive(CandidType)]
+struct NarrowVoteRecord {
+    choice: VoteTypes,
+    timestamp: u64,
+    cast: NarrowTally,
+    ballots: u32,
+}
+
+#[derive(CandidType)]
+struct NarrowSnapshot {
+    tally: NarrowTally,
+    voters: u32,
+}
+
+fn record_tally() -> NarrowTally {
+    NarrowTally {
+        approve: 0,
+        reject: 9,
+        pass: 0,
+    }
+}
+
+#[test]
+fn weights_of_older_releases_move_to_the_wider_maps() {
+    let memory = MEMORY_MANAGER.with(|m| m.borrow().get(memory::LEGACY_VOTER_WEIGHTS));
+    let mut old: StableBTreeMap<StorablePrincipal, u32, Memory> =
+        StableBTreeMap::init(memory.clone());
+    old.insert(StorablePrincipal(user(2)), u32::MAX);
+    upgrade();
+    let weight = VOTER_WEIGHTS.with(|w| w.borrow().get(&StorablePrincipal(user(2))));
+    assert_eq!(weight, Some(u64::from(u32::MAX)));
+    let old: StableBTreeMap<StorablePrincipal, u32, Memory> = StableBTreeMap::init(memory);
+    assert!(old.is_empty());
+}
+
+#[test]
+fn narrow_counters_of_older_releases_still_decode() {
+    let record = NarrowVoteRecord {
+        choice: VoteTypes::Reject,
+        timestamp: 5,
+        cast: record_tally(),
+        ballots: 1,
+    };
+    let record = VoteRecord::from_bytes(Cow::Owned(Encode!(&record).unwrap()));
+    assert_eq!(record.cast.reject, 9);
+    let snapshot = NarrowSnapshot {
+        tally: record_tally(),
+        voters: 1,
+    };
+    let snapshot = TallySnapshot::from_bytes(Cow::Owned(Encode!(&snapshot).unwrap()));
+    assert_eq!(snapshot.tally.reject, 9);
+}
+
+// A snapshot from before private-tally ballots were recorded.
+#[derive(CandidType)]
+struct NamedOnlySnapshot {
+    tally: Tally,
+    voters: u32,
+}
+
+#[test]
+fn snapshots_without_private_ballots_still_decode() {
+    let snapshot = NamedOnlySnapshot {
+        tally: Tally { approve: 3, ..Tally::default() },
+        voters: 3,
+    };
+    let snapshot = TallySnapshot::from_bytes(Cow::Owned(Encode!(&snapshot).unwrap()));
+    assert_eq!((snapshot.tally.approve, snapshot.anonymous_ballots), (3, None));
+    assert_eq!(snapshot.voter_count(), 3);
+}
@@ -0,0 +1,232 @@
+use super::*;
+
+#[test]
+fn voters_are_listed_page_by_page_in_voting_order() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    for voter in 10..15 {
+        cast(1, user(voter), VoteTypes::Approve).unwrap();
+    }
+    assert_eq!(get_voters(1, 0, 2), vec![user(10), user(11)]);
+    assert_eq!(get_voters(1, 4, 2), vec![user(14)]);
+    assert!(get_voters(1, 5, 2).is_empty());
+    assert!(get_voters(2, 0, 2).is_empty());
+}
+
+#[test]
+fn voters_of_private_proposals_are_not_listed() {
+    setup();
+    let private = CreateProposal {
+        private_voters: Some(true),
+        ..proposal("Pick a venue")
+    };
+    as_user(user(1), || create_proposal(1, private)).unwrap();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert!(get_voters(1, 0, 10).is_empty());
+}
+
+#[test]
+fn private_voters_stay_private_once_someone_voted() {
+    setup();
+    let private = CreateProposal {
+        private_voters: Some(true),
+        ..proposal("Pick a venue")
+    };
+    assert_eq!(try_create(1, user(1), private), Ok(()));
+    let public = CreateProposal {
+        private_voters: Some(false),
+        ..proposal("Pick a venue")
+    };
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    let edited = as_user(user(1), || edit_proposal(1, public));
+    assert_eq!(edited, Err(VoteError::ProposalLocked));
+    assert!(get_voters(1, 0, 10).is_empty());
+    // Going private is always allowed.
+    assert_eq!(try_create(2, user(1), proposal("Pick a date")), Ok(()));
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    let private = CreateProposal {
+        private_voters: Some(true),
+        ..proposal("Pick a date")
+    };
+    assert_eq!(as_user(user(1), || edit_proposal(2, private)), Ok(()));
+    assert!(get_voters(2, 0, 10).is_empty());
+}
+
+#[test]
+fn display_names_resolve_per_principal() {
+    setup();
+    assert_eq!(as_user(user(2), || set_display_name("  Ada ".to_string())), Ok(()));
+    assert_eq!(get_display_name(user(2)), Some("Ada".to_string()));
+    assert_eq!(get_display_name(user(3)), None);
+}
+
+#[test]
+fn display_names_must_be_short_and_not_blank() {
+    let longest = "n".repeat(MAX_DISPLAY_NAME_LEN as usize);
+    assert_eq!(validate_display_name(longest.clone()), Ok(longest.clone()));
+    let too_long = format!("{longest}n");
+    assert_eq!(validate_display_name(too_long), Err(VoteError::InvalidDisplayName));
+    assert_eq!(validate_display_name("   ".to_string()), Err(VoteError::InvalidDisplayName));
+    setup();
+    let rejected = as_user(user(2), || set_display_name("n".repeat(100)));
+    assert_eq!(rejected, Err(VoteError::InvalidDisplayName));
+    assert_eq!(get_display_name(user(2)), None);
+}
+
+fn register(who: Principal, name: &str) -> Result<(), VoteError> {
+    let profile = VoterProfile {
+        display_name: name.to_string(),
+    };
+    as_user(who, || register_voter(profile))
+}
+
+#[test]
+fn unregistered_voters_are_refused_only_under_the_policy() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(set_require_registration(true), Ok(()));
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Err(VoteError::NotRegistered));
+    assert_eq!(register(user(3), "Grace"), Ok(()));
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Ok(()));
+    assert_eq!(set_require_registration(false), Ok(()));
+    assert_eq!(cast(1, user(4), VoteTypes::Approve), Ok(()));
+}
+
+#[test]
+fn registering_again_keeps_the_registration_time() {
+    setup();
+    assert_eq!(register(user(2), "Grace"), Ok(()));
+    env::advance_time(1_000);
+    assert_eq!(register(user(2), "Ada"), Ok(()));
+    let voter = VOTER_MAP.with(|v| v.borrow().get(&StorablePrincipal(user(2)))).unwrap();
+    assert_eq!(voter.registered_at, env::START_TIME);
+    assert_eq!(voter.display_name, "Ada");
+    assert_eq!(register(user(3), " "), Err(VoteError::InvalidDisplayName));
+}
+
+#[test]
+fn only_controllers_set_the_registration_policy() {
+    setup();
+    let refused = as_user(user(2), || set_require_registration(true));
+    assert_eq!(refused, Err(VoteError::Unauthorized));
+    assert!(!config().require_registration);
+}
+
+#[test]
+fn voters_by_choice_partition_the_voters() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    cast(1, user(12), VoteTypes::Approve).unwrap();
+    cast(1, user(10), VoteTypes::Reject).unwrap();
+    cast(1, user(11), VoteTypes::Approve).unwrap();
+    cast(1, user(13), VoteTypes::Pass).unwrap();
+    assert_eq!(get_voters_by_choice(1, VoteTypes::Approve), vec![user(11), user(12)]);
+    assert_eq!(get_voters_by_choice(1, VoteTypes::Reject), vec![user(10)]);
+    assert_eq!(get_voters_by_choice(1, VoteTypes::Pass), vec![user(13)]);
+    assert!(get_voters_by_choice(2, VoteTypes::Approve).is_empty());
+}
+
+#[test]
+fn voters_by_choice_of_private_proposals_are_not_listed() {
+    setup();
+    let private = CreateProposal {
+        private_voters: Some(true),
+        ..proposal("Pick a venue")
+    };
+    assert_eq!(try_create(1, user(1), private), Ok(()));
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert!(get_voters_by_choice(1, VoteTypes::Approve).is_empty());
+}
+
+#[test]
+fn subscribing_twice_counts_once() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    create(2, user(1), "Pick a date");
+    assert_eq!(as_user(user(2), || subscribe(1)), Ok(()));
+    assert_eq!(as_user(user(2), || subscribe(1)), Ok(()));
+    as_user(user(3), || subscribe(2)).unwrap();
+    assert_eq!(get_subscriber_count(1), 1);
+    assert_eq!(get_subscribers(1, 0, 10), vec![user(2)]);
+    as_user(user(2), || unsubscribe(1));
+    assert_eq!(get_subscriber_count(1), 0);
+    assert_eq!(get_subscriber_count(2), 1);
+    assert_eq!(as_user(user(2), || subscribe(3)), Err(VoteError::NoProposal));
+}
+
+#[test]
+fn eligible_voters_are_replaced_as_a_whole() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    let replace = |voters: Vec<u8>| {
+        let voters = voters.into_iter().map(user).collect();
+        as_user(user(1), || set_eligible_voters(1, voters))
+    };
+    assert_eq!(replace(vec![2, 3, 4]), Ok(()));
+    assert_eq!(replace(vec![4, 5, 6, 7, 8, 8, 5]), Ok(()));
+    assert_eq!(eligible_count(1), 5);
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Err(VoteError::NotEligible));
+    assert_eq!(cast(1, user(4), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(1, user(8), VoteTypes::Approve), Ok(()));
+    let refused = as_user(user(2), || set_eligible_voters(1, vec![user(2)]));
+    assert_eq!(refused, Err(VoteError::Unauthorized));
+}
+
+#[test]
+#[should_panic(expected = "too many voters")]
+fn eligible_voters_are_bounded() {
+    setup();
+    create(1, user(1), "Pick a venue");
+    let voters = vec![user(2); MAX_ELIGIBLE_BATCH_SIZE + 1];
+    let _ = as_user(user(1), || set_eligible_voters(1, voters));
+}
+
+#[test]
+fn anonymous_callers_are_refused_by_default() {
+    setup();
+    let anonymous = Principal::anonymous();
+    let created = try_create(1, anonymous, proposal("Pick a venue"));
+    assert_eq!(created, Err(VoteError::Unauthorized));
+    create(1, user(1), "Pick a venue");
+    assert_eq!(cast(1, anonymous, VoteTypes::Approve), Err(VoteError::Unauthorized));
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(set_allow_anonymous(true), Ok(()));
+    assert_eq!(cast(1, anonymous, VoteTypes::Approve), Ok(()));
+    assert_eq!(try_create(2, anonymous, proposal("Pick a date")), Ok(()));
+}
+
+#[test]
+fn new_accounts_wait_out_the_minimum_age() {
+    setup();
+    set_min_account_age(1_000).unwrap();
+    create(1, user(1), "Pick a venue");
+    as_user(user(2), || subscribe(1)).unwrap();
+    env::advance_time(1_000);
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    // A refused ballot still counts as the first interaction.
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Err(VoteError::AccountTooNew));
+    env::advance_time(999);
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Err(VoteError::AccountTooNew));
+    env::advance_time(1);
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Ok(()));
+}
+
+#[test]
+fn aliases_share_one_ballot_when_normalized() {
+    setup();
+    set_principal_alias(user(3), Some(user(2))).unwrap();
+    let linked = as_user(user(2), || set_principal_alias(user(4), Some(user(2))));
+    assert_eq!(linked, Err(VoteError::Unauthorized));
+    create(1, user(1), "Pick a venue");
+    create(2, user(1), "Pick a date");
+    // Without the flag the alias is a voter of its own.
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Ok(()));
+    set_normalize_principals(true).unwrap();
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(2, user(3), VoteTypes::Reject), Err(VoteError::AlreadyVoted));
+    assert_eq!(get_proposal(2).unwrap().approve, 1);
+    set_principal_alias(user(3), None).unwrap();
+    assert_eq!(cast(2, user(3), VoteTypes::Reject), Ok(()));
+}
@@ -0,0 +1,381 @@
+use super::*;
+
+#[test]
+fn vote_many_reports_each_proposal() {
+    setup();
+    for key in 1..=5 {
+        create(key, user(1), &format!("Budget line {key}"));
+    }
+    cast(2, user(2), VoteTypes::Reject).unwrap();
+    cast(4, user(2), VoteTypes::Reject).unwrap();
+    let results = as_user(user(2), || vote_many(vec![1, 2, 3, 4, 5, 6], VoteTypes::Approve));
+    assert_eq!(
+        results,
+        vec![
+            Ok(()),
+            Err(VoteError::AlreadyVoted),
+            Ok(()),
+            Err(VoteError::AlreadyVoted),
+            Ok(()),
+            Err(VoteError::NoProposal),
+        ]
+    );
+    assert_eq!(get_proposal(2).unwrap().approve, 0);
+    assert_eq!(get_proposal(5).unwrap().approve, 1);
+}
+
+#[test]
+#[should_panic(expected = "too many keys")]
+fn vote_many_bounds_its_input() {
+    setup();
+    vote_many((0..=MAX_BATCH_SIZE as u64).collect(), VoteTypes::Approve);
+}
+
+#[test]
+fn recent_votes_can_be_cancelled_within_the_window() {
+    setup();
+    set_vote_cancellation_window(30).unwrap();
+    create(1, user(1), "Extend the library hours");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(30);
+    assert_eq!(as_user(user(2), || cancel_recent_vote(1)), Ok(()));
+    let proposal = get_proposal(1).unwrap();
+    assert_eq!((proposal.approve, proposal.voted.len()), (0, 0));
+    assert_eq!(as_user(user(2), || cancel_recent_vote(1)), Err(VoteError::NotVoted));
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    assert_eq!(get_proposal(1).unwrap().reject, 1);
+}
+
+#[test]
+fn votes_past_the_window_stay() {
+    setup();
+    set_vote_cancellation_window(30).unwrap();
+    create(1, user(1), "Extend the library hours");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    env::advance_time(31);
+    let cancelled = as_user(user(2), || cancel_recent_vote(1));
+    assert_eq!(cancelled, Err(VoteError::CancellationWindowClosed));
+    assert_eq!(get_proposal(1).unwrap().approve, 1);
+}
+
+fn binary(key: u64) {
+    let settings = CreateProposal {
+        binary: Some(true),
+        ..proposal("Adopt the charter")
+    };
+    as_user(user(1), || create_proposal(key, settings)).unwrap();
+}
+
+#[test]
+fn binary_proposals_refuse_pass() {
+    setup();
+    binary(1);
+    assert_eq!(cast(1, user(2), VoteTypes::Pass), Err(VoteError::InvalidOption));
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Reject).unwrap();
+    let proposal = get_proposal(1).unwrap();
+    assert_eq!((proposal.approve, proposal.reject, proposal.pass), (1, 1, 0));
+}
+
+#[test]
+fn binary_percentages_leave_pass_out() {
+    let tally = Tally {
+        approve: 3,
+        reject: 1,
+        pass: 4,
+    };
+    assert_eq!(percentages(&tally, true), (75.0, 25.0, 0.0));
+    assert_eq!(percentages(&tally, false), (37.5, 12.5, 50.0));
+    assert_eq!(percentages(&Tally::default(), false), (0.0, 0.0, 0.0));
+}
+
+fn receipt(key: u64, who: Principal, choice: VoteTypes) -> Result<VoteReceipt, VoteError> {
+    as_user(who, || block_on(vote_with_receipt(key, choice, None)))
+}
+
+#[test]
+fn receipts_carry_the_tally_after_the_vote() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    env::advance_time(10);
+    let receipt = receipt(1, user(3), VoteTypes::Approve).unwrap();
+    assert_eq!(receipt.proposal_key, 1);
+    assert!(matches!(receipt.choice, VoteTypes::Approve));
+    assert_eq!(receipt.timestamp, env::time());
+    assert_eq!(get_tally(1), Some(receipt.tally_after));
+    assert_eq!(receipt.tally_after.approve, 1);
+    assert_eq!(receipt.tally_after.reject, 1);
+}
+
+#[test]
+fn failed_votes_return_no_receipt() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    receipt(1, user(2), VoteTypes::Approve).unwrap();
+    let again = receipt(1, user(2), VoteTypes::Approve).err();
+    assert_eq!(again, Some(VoteError::AlreadyVoted));
+    assert_eq!(receipt(2, user(2), VoteTypes::Approve).err(), Some(VoteError::NoProposal));
+}
+
+fn cast_with(key: u64, who: Principal, secret: Option<&str>) -> Result<(), VoteError> {
+    let secret = secret.map(str::to_string);
+    as_user(who, || block_on(vote(key, VoteTypes::Approve, secret, None)))
+}
+
+#[test]
+fn shared_polls_need_the_matching_secret() {
+    setup();
+    let poll = CreateProposal {
+        secret: Some("lunch".to_string()),
+        ..proposal("Order pizza")
+    };
+    assert_eq!(try_create(1, user(1), poll), Ok(()));
+    assert_eq!(cast_with(1, user(2), None), Err(VoteError::Unauthorized));
+    assert_eq!(cast_with(1, user(2), Some("Lunch")), Err(VoteError::Unauthorized));
+    assert_eq!(cast_with(1, user(2), Some("")), Err(VoteError::Unauthorized));
+    assert_eq!(cast_with(1, user(2), Some("lunch")), Ok(()));
+    assert_eq!(get_proposal(1).unwrap().secret_hash, None);
+}
+
+#[test]
+fn polls_without_a_secret_ignore_one() {
+    setup();
+    let open = CreateProposal {
+        secret: Some(String::new()),
+        ..proposal("Order pizza")
+    };
+    assert_eq!(try_create(1, user(1), open), Ok(()));
+    assert_eq!(cast_with(1, user(2), Some("anything")), Ok(()));
+    assert_eq!(cast_with(1, user(3), None), Ok(()));
+}
+
+#[test]
+fn frozen_canister_refuses_updates_but_answers_queries() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    assert_eq!(as_user(user(1), freeze_canister), Err(VoteError::Unauthorized));
+    assert_eq!(freeze_canister(), Ok(()));
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Err(VoteError::CanisterFrozen));
+    let created = try_create(2, user(1), proposal("Adopt the budget"));
+    assert_eq!(created, Err(VoteError::CanisterFrozen));
+    let edited = as_user(user(1), || edit_proposal(1, proposal("Adopt the new roadmap")));
+    assert_eq!(edited, Err(VoteError::CanisterFrozen));
+    assert_eq!(as_user(user(1), || end_proposal(1)), Err(VoteError::CanisterFrozen));
+    assert_eq!(get_proposal(1).unwrap().description, "Adopt the roadmap");
+    assert_eq!(unfreeze_canister(), Ok(()));
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+}
+
+fn cast_once(key: u64, who: Principal, idempotency_key: &[u8]) -> Result<(), VoteError> {
+    let idempotency_key = Some(idempotency_key.to_vec());
+    as_user(who, || block_on(vote(key, VoteTypes::Approve, None, idempotency_key)))
+}
+
+#[test]
+fn retried_votes_with_the_same_key_count_once() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    create(2, user(1), "Adopt the budget");
+    assert_eq!(cast_once(1, user(2), b"retry-1"), Ok(()));
+    assert_eq!(cast_once(1, user(2), b"retry-1"), Ok(()));
+    assert_eq!(get_proposal(1).unwrap().approve, 1);
+    assert_eq!(cast_once(1, user(2), b"retry-2"), Err(VoteError::AlreadyVoted));
+    // The same client key on another proposal is a new ballot.
+    assert_eq!(cast_once(2, user(2), b"retry-1"), Ok(()));
+    assert_eq!(get_proposal(2).unwrap().approve, 1);
+}
+
+fn round(key: u64, round: u64) -> Option<(Tally, u32)> {
+    get_round(key, round).map(|snapshot| (snapshot.tally, snapshot.voters))
+}
+
+#[test]
+fn each_round_keeps_its_own_tally() {
+    setup();
+    create(1, user(1), "Pick this week's lunch spot");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Approve).unwrap();
+    assert_eq!(as_user(user(2), || snapshot_and_reset(1)).err(), Some(VoteError::Unauthorized));
+    assert!(as_user(user(1), || snapshot_and_reset(1)).is_ok());
+    assert_eq!(get_proposal(1).unwrap().approve, 0);
+    // Last week's voters get a fresh ballot.
+    cast(1, user(2), VoteTypes::Reject).unwrap();
+    assert!(as_user(user(1), || snapshot_and_reset(1)).is_ok());
+    let first = Tally { approve: 2, ..Tally::default() };
+    let second = Tally { reject: 1, ..Tally::default() };
+    assert_eq!(round(1, 0), Some((first, 2)));
+    assert_eq!(round(1, 1), Some((second, 1)));
+    assert_eq!(round(1, 2), None);
+    assert_eq!(as_user(user(1), || snapshot_and_reset(9)).err(), Some(VoteError::NoProposal));
+}
+
+#[test]
+fn closed_proposals_have_no_next_round() {
+    setup();
+    create(1, user(1), "Pick this week's lunch spot");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(1), || end_proposal(1)).unwrap();
+    let reset = as_user(user(1), || snapshot_and_reset(1)).err();
+    assert_eq!(reset, Some(VoteError::ProposalNotActive));
+    assert_eq!(get_proposal(1).unwrap().approve, 1);
+    assert!(get_round(1, 0).is_none());
+}
+
+fn confirm(who: Principal, token: VoteToken) -> Result<(), VoteError> {
+    as_user(who, || block_on(confirm_vote(token)))
+}
+
+#[test]
+fn confirmed_tokens_apply_the_prepared_vote() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    let token = as_user(user(2), || prepare_vote(1, VoteTypes::Reject)).unwrap();
+    assert_eq!(get_proposal(1).unwrap().reject, 0);
+    assert_eq!(confirm(user(3), token), Err(VoteError::InvalidVoteToken));
+    assert_eq!(confirm(user(2), token), Ok(()));
+    assert_eq!(get_proposal(1).unwrap().reject, 1);
+    assert_eq!(confirm(user(2), token), Err(VoteError::InvalidVoteToken));
+    assert_eq!(as_user(user(2), || prepare_vote(9, VoteTypes::Reject)), Err(VoteError::NoProposal));
+}
+
+#[test]
+fn expired_tokens_are_refused() {
+    setup();
+    set_vote_confirmation_window(1_000).unwrap();
+    create(1, user(1), "Adopt the roadmap");
+    let token = as_user(user(2), || prepare_vote(1, VoteTypes::Approve)).unwrap();
+    env::advance_time(1_000);
+    assert_eq!(confirm(user(2), token), Err(VoteError::VoteTokenExpired));
+    assert_eq!(get_proposal(1).unwrap().approve, 0);
+    assert_eq!(confirm(user(2), token), Err(VoteError::InvalidVoteToken));
+}
+
+#[test]
+fn a_second_vote_waits_for_the_first_to_finish() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    create(2, user(1), "Adopt the budget");
+    // The fake replica never suspends a call, so hold the guard a vote takes
+    // across its await by hand.
+    let first = InFlightVote::acquire(user(2), 1).unwrap();
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Err(VoteError::VoteInProgress));
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(1, user(3), VoteTypes::Approve), Ok(()));
+    drop(first);
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(get_proposal(1).unwrap().approve, 2);
+    // Refused votes release the guard too.
+    assert_eq!(cast(1, user(2), VoteTypes::Reject), Err(VoteError::AlreadyVoted));
+    assert_eq!(cast(1, user(2), VoteTypes::Reject), Err(VoteError::AlreadyVoted));
+    assert!(VOTES_IN_FLIGHT.with(|v| v.borrow().is_empty()));
+}
+
+fn grouped(key: u64, owner: Principal, group: Option<u64>) {
+    let settings = CreateProposal {
+        vote_group_id: group,
+        ..proposal(&format!("Motion part {key}"))
+    };
+    assert_eq!(try_create(key, owner, settings), Ok(()));
+}
+
+#[test]
+fn one_ballot_covers_the_whole_vote_group() {
+    setup();
+    grouped(1, user(1), Some(7));
+    grouped(2, user(1), Some(7));
+    grouped(3, user(1), Some(8));
+    grouped(4, user(1), None);
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Err(VoteError::AlreadyVoted));
+    assert_eq!(cast(3, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(4, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(2, user(3), VoteTypes::Reject), Ok(()));
+    // Deleting the sibling frees the group for its voters.
+    assert_eq!(bulk_delete_proposals(vec![1]), vec![Ok(())]);
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Ok(()));
+}
+
+#[test]
+fn vote_groups_belong_to_their_owner() {
+    setup();
+    grouped(1, user(1), Some(9));
+    grouped(2, user(6), Some(9));
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+    // Handing proposals over joins them to the new owner's group.
+    grouped(3, user(1), Some(9));
+    assert_eq!(transfer_all_proposals(user(6), user(1)), Ok(1));
+    assert_eq!(cast(3, user(3), VoteTypes::Approve), Ok(()));
+    assert_eq!(cast(2, user(3), VoteTypes::Approve), Err(VoteError::AlreadyVoted));
+}
+
+#[test]
+fn archived_siblings_leave_the_group_until_restored() {
+    setup();
+    grouped(1, user(1), Some(7));
+    grouped(2, user(1), Some(7));
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(1), || end_proposal(1)).unwrap();
+    as_user(user(1), || archive_proposal(1)).unwrap();
+    assert_eq!(as_user(user(1), || restore_proposal(1)), Ok(()));
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Err(VoteError::AlreadyVoted));
+    VOTE_GROUPS.with(|g| g.borrow_mut().clear());
+    post_upgrade();
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Err(VoteError::AlreadyVoted));
+    assert_eq!(as_user(user(1), || archive_proposal(1)), Ok(()));
+    assert_eq!(cast(2, user(2), VoteTypes::Approve), Ok(()));
+}
+
+#[test]
+fn ballots_count_down_the_allowance() {
+    setup();
+    set_votes_per_principal(3).unwrap();
+    create(1, user(1), "Adopt the roadmap");
+    assert_eq!(get_remaining_allowance(1, user(2)), 3);
+    for remaining in (0..3).rev() {
+        assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+        assert_eq!(get_remaining_allowance(1, user(2)), remaining);
+    }
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Err(VoteError::AllowanceExhausted));
+    assert_eq!(get_remaining_allowance(1, user(3)), 3);
+    assert_eq!(set_votes_per_principal(0), Err(VoteError::InvalidOption));
+}
+
+fn invalidations(key: u64) -> usize {
+    let events = get_proposal_events(key, 0, 10);
+    events.iter().filter(|event| matches!(event.kind, EventKind::VotesInvalidated)).count()
+}
+
+#[test]
+fn description_edits_can_invalidate_votes() {
+    setup();
+    set_invalidate_votes_on_edit(true).unwrap();
+    set_record_vote_timeline(true).unwrap();
+    create(1, user(1), "Adopt the roadmap");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Reject).unwrap();
+    // Only a new description drops the votes.
+    as_user(user(1), || edit_proposal(1, proposal("Adopt the roadmap"))).unwrap();
+    assert_eq!(get_proposal(1).unwrap().voted.len(), 2);
+    as_user(user(1), || edit_proposal(1, proposal("Adopt the new roadmap"))).unwrap();
+    let stored = get_proposal(1).unwrap();
+    assert_eq!((stored.approve, stored.reject), (0, 0));
+    assert!(stored.voted.is_empty());
+    assert!(VOTE_MAP.with(|v| v.borrow().get(&(1, StorablePrincipal(user(2)))).is_none()));
+    assert_eq!(invalidations(1), 1);
+    assert!(get_vote_timeline(1, 0, 10).is_empty());
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Ok(()));
+}
+
+#[test]
+fn description_edits_keep_votes_by_default() {
+    setup();
+    create(1, user(1), "Adopt the roadmap");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    as_user(user(1), || edit_proposal(1, proposal("Adopt the new roadmap"))).unwrap();
+    let stored = get_proposal(1).unwrap();
+    assert_eq!((stored.approve, stored.voted), (1, vec![user(2)]));
+    assert_eq!(invalidations(1), 0);
+    assert_eq!(cast(1, user(2), VoteTypes::Approve), Err(VoteError::AlreadyVoted));
+}
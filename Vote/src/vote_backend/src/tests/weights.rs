@@ -0,0 +1,79 @@
+use super::*;
+
+fn weighted(key: u64) {
+    let settings = CreateProposal {
+        weighted: Some(true),
+        ..proposal("Raise the budget")
+    };
+    assert_eq!(try_create(key, user(1), settings), Ok(()));
+}
+
+#[test]
+fn weights_are_snapshotted_when_the_proposal_is_created() {
+    setup();
+    set_voter_weight(user(2), 5).unwrap();
+    set_voter_weight(user(3), 2).unwrap();
+    weighted(1);
+    set_voter_weight(user(2), 50).unwrap();
+    set_voter_weight(user(3), 0).unwrap();
+    set_voter_weight(user(4), 7).unwrap();
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Reject).unwrap();
+    assert_eq!(cast(1, user(4), VoteTypes::Approve), Err(VoteError::NoVotingPower));
+    let stored = get_proposal(1).unwrap();
+    assert_eq!((stored.approve, stored.reject), (5, 2));
+    weighted(2);
+    cast(2, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal(2).unwrap().approve, 50);
+}
+
+#[test]
+fn unweighted_proposals_count_every_vote_once() {
+    setup();
+    set_voter_weight(user(2), 5).unwrap();
+    create(1, user(1), "Raise the budget");
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    cast(1, user(3), VoteTypes::Approve).unwrap();
+    assert_eq!(get_proposal(1).unwrap().approve, 2);
+}
+
+#[test]
+fn only_controllers_set_weights() {
+    setup();
+    let refused = as_user(user(2), || set_voter_weight(user(2), 9));
+    assert_eq!(refused, Err(VoteError::Unauthorized));
+    assert_eq!(VOTER_WEIGHTS.with(|w| w.borrow().get(&StorablePrincipal(user(2)))), None);
+}
+
+fn weight_of(who: Principal, key: u64) -> u64 {
+    as_user(who, || effective_weight(key))
+}
+
+#[test]
+fn effective_weight_follows_the_weighting_in_play() {
+    setup();
+    set_voter_weight(user(2), 5).unwrap();
+    create(1, user(1), "Raise the budget");
+    weighted(2);
+    set_voter_weight(user(2), 50).unwrap();
+    assert_eq!(weight_of(user(2), 1), 1);
+    assert_eq!(weight_of(user(2), 2), 5);
+    assert_eq!(weight_of(user(3), 2), 0);
+    assert_eq!(weight_of(user(2), 9), 0);
+    // An alias carries its base's weight once principals are normalized.
+    set_principal_alias(user(3), Some(user(2))).unwrap();
+    set_normalize_principals(true).unwrap();
+    assert_eq!(weight_of(user(3), 2), 5);
+}
+
+#[test]
+fn effective_neuron_power_is_known_after_the_first_fetch() {
+    setup();
+    let source = Principal::from_slice(&[0xcc; 10]);
+    set_neuron_source(Some(source)).unwrap();
+    env::on_call(source, "get_voting_power", |_| Ok(Encode!(&7u64).unwrap()));
+    weighted(1);
+    assert_eq!(weight_of(user(2), 1), 0);
+    cast(1, user(2), VoteTypes::Approve).unwrap();
+    assert_eq!(weight_of(user(2), 1), 7);
+}